@@ -0,0 +1,285 @@
+use std::mem;
+use std::os::raw::c_void;
+
+use ash::vk;
+
+use crate::buffer::{Buffer, BufferBuilder, BufferType, IndexType};
+use crate::device::VulkanDevice;
+use crate::errors::VulkanError;
+use crate::vulkan_context::VulkanContext;
+use crate::Shared;
+
+pub struct AccelerationStructure {
+    device: Shared<VulkanDevice>,
+    acceleration_structure: vk::AccelerationStructureNV,
+    memory: vk::DeviceMemory,
+    handle: u64,
+}
+
+impl Drop for AccelerationStructure {
+    fn drop(&mut self) {
+        self.device
+            .destroy_acceleration_structure(self.acceleration_structure);
+        self.device.free_memory(self.memory);
+    }
+}
+
+impl AccelerationStructure {
+    pub fn get(&self) -> vk::AccelerationStructureNV {
+        self.acceleration_structure
+    }
+
+    pub fn get_handle(&self) -> u64 {
+        self.handle
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct GeometryInstance {
+    pub transform: [f32; 12],
+    instance_custom_index_and_mask: u32,
+    instance_offset_and_flags: u32,
+    acceleration_structure_handle: u64,
+}
+
+impl GeometryInstance {
+    pub fn new(
+        transform: [f32; 12],
+        custom_index: u32,
+        mask: u8,
+        hit_group_offset: u32,
+        flags: vk::GeometryInstanceFlagsNV,
+        bottom_level: &AccelerationStructure,
+    ) -> Self {
+        GeometryInstance {
+            transform,
+            instance_custom_index_and_mask: (custom_index & 0x00ff_ffff) | (u32::from(mask) << 24),
+            instance_offset_and_flags: (hit_group_offset & 0x00ff_ffff)
+                | ((flags.as_raw() & 0xff) << 24),
+            acceleration_structure_handle: bottom_level.get_handle(),
+        }
+    }
+}
+
+pub struct BottomLevelAccelerationStructureBuilder<'a> {
+    context: &'a VulkanContext,
+    geometries: Vec<vk::GeometryNV>,
+    flags: vk::BuildAccelerationStructureFlagsNV,
+}
+
+impl<'a> BottomLevelAccelerationStructureBuilder<'a> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        BottomLevelAccelerationStructureBuilder {
+            context,
+            geometries: vec![],
+            flags: vk::BuildAccelerationStructureFlagsNV::PREFER_FAST_TRACE,
+        }
+    }
+
+    /// Vertices are read as tightly packed `R32G32B32_SFLOAT` positions at the start of each
+    /// `vertex_stride` bytes. The buffers are borrowed for as long as the builder, which only
+    /// keeps their handles.
+    pub fn with_triangles(
+        mut self,
+        vertex_buffer: &'a Buffer,
+        vertex_count: u32,
+        vertex_stride: vk::DeviceSize,
+        index_buffer: &'a Buffer,
+        index_count: u32,
+        index_type: IndexType,
+    ) -> Self {
+        let triangles = vk::GeometryTrianglesNV::builder()
+            .vertex_data(vertex_buffer.get())
+            .vertex_offset(0)
+            .vertex_count(vertex_count)
+            .vertex_stride(vertex_stride)
+            .vertex_format(vk::Format::R32G32B32_SFLOAT)
+            .index_data(index_buffer.get())
+            .index_offset(0)
+            .index_count(index_count)
+            .index_type(index_type.into())
+            .build();
+
+        let geometry = vk::GeometryNV::builder()
+            .geometry_type(vk::GeometryTypeNV::TRIANGLES)
+            .geometry(
+                vk::GeometryDataNV::builder()
+                    .triangles(triangles)
+                    .aabbs(vk::GeometryAABBNV::default())
+                    .build(),
+            )
+            .flags(vk::GeometryFlagsNV::OPAQUE)
+            .build();
+
+        self.geometries.push(geometry);
+        self
+    }
+
+    pub fn with_flags(mut self, flags: vk::BuildAccelerationStructureFlagsNV) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn build(self) -> Result<AccelerationStructure, VulkanError> {
+        if self.geometries.is_empty() {
+            return Err(VulkanError::AccelerationStructureCreationError(
+                String::from("No geometry provided"),
+            ));
+        }
+
+        let info = vk::AccelerationStructureInfoNV::builder()
+            .ty(vk::AccelerationStructureTypeNV::BOTTOM_LEVEL)
+            .flags(self.flags)
+            .geometries(&self.geometries)
+            .build();
+
+        build_acceleration_structure(self.context, &info, vk::Buffer::null())
+    }
+}
+
+pub struct TopLevelAccelerationStructureBuilder<'a> {
+    context: &'a VulkanContext,
+    instances: Vec<GeometryInstance>,
+    flags: vk::BuildAccelerationStructureFlagsNV,
+}
+
+impl<'a> TopLevelAccelerationStructureBuilder<'a> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        TopLevelAccelerationStructureBuilder {
+            context,
+            instances: vec![],
+            flags: vk::BuildAccelerationStructureFlagsNV::PREFER_FAST_TRACE,
+        }
+    }
+
+    pub fn with_instances(mut self, instances: &[GeometryInstance]) -> Self {
+        self.instances.extend_from_slice(instances);
+        self
+    }
+
+    pub fn with_flags(mut self, flags: vk::BuildAccelerationStructureFlagsNV) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn build(self) -> Result<AccelerationStructure, VulkanError> {
+        if self.instances.is_empty() {
+            return Err(VulkanError::AccelerationStructureCreationError(
+                String::from("No instance provided"),
+            ));
+        }
+
+        let instance_buffer = BufferBuilder::new(self.context)
+            .with_type(BufferType::RayTracingInstance)
+            .with_size((self.instances.len() * mem::size_of::<GeometryInstance>()) as u64)
+            .build()?;
+        instance_buffer.copy_data(self.instances.as_ptr() as *const c_void)?;
+
+        let info = vk::AccelerationStructureInfoNV::builder()
+            .ty(vk::AccelerationStructureTypeNV::TOP_LEVEL)
+            .flags(self.flags)
+            .instance_count(self.instances.len() as u32)
+            .build();
+
+        build_acceleration_structure(self.context, &info, instance_buffer.get())
+    }
+}
+
+fn build_acceleration_structure(
+    context: &VulkanContext,
+    info: &vk::AccelerationStructureInfoNV,
+    instance_buffer: vk::Buffer,
+) -> Result<AccelerationStructure, VulkanError> {
+    let device = context.get_device();
+
+    let create_info = vk::AccelerationStructureCreateInfoNV::builder()
+        .info(*info)
+        .build();
+    let acceleration_structure = device.create_acceleration_structure(&create_info)?;
+
+    let memory = match allocate_memory(context, acceleration_structure) {
+        Ok(memory) => memory,
+        Err(err) => {
+            device.destroy_acceleration_structure(acceleration_structure);
+            return Err(err);
+        }
+    };
+
+    let mut acceleration_structure = AccelerationStructure {
+        device: Shared::clone(device),
+        acceleration_structure,
+        memory,
+        handle: 0,
+    };
+    acceleration_structure.handle =
+        device.get_acceleration_structure_handle(acceleration_structure.get())?;
+
+    let scratch_requirements = device.get_acceleration_structure_memory_requirements(
+        &vk::AccelerationStructureMemoryRequirementsInfoNV::builder()
+            .ty(vk::AccelerationStructureMemoryRequirementsTypeNV::BUILD_SCRATCH)
+            .acceleration_structure(acceleration_structure.get())
+            .build(),
+    )?;
+    let scratch_buffer = BufferBuilder::new(context)
+        .with_type(BufferType::RayTracing)
+        .with_size(scratch_requirements.size)
+        .build()?;
+
+    let command_buffer = context.begin_single_time_commands()?;
+    device.cmd_build_acceleration_structure(
+        command_buffer,
+        info,
+        instance_buffer,
+        false,
+        acceleration_structure.get(),
+        vk::AccelerationStructureNV::null(),
+        scratch_buffer.get(),
+    )?;
+    context.end_single_time_commands(command_buffer)?;
+
+    Ok(acceleration_structure)
+}
+
+fn allocate_memory(
+    context: &VulkanContext,
+    acceleration_structure: vk::AccelerationStructureNV,
+) -> Result<vk::DeviceMemory, VulkanError> {
+    let device = context.get_device();
+
+    let mem_requirements = device.get_acceleration_structure_memory_requirements(
+        &vk::AccelerationStructureMemoryRequirementsInfoNV::builder()
+            .ty(vk::AccelerationStructureMemoryRequirementsTypeNV::OBJECT)
+            .acceleration_structure(acceleration_structure)
+            .build(),
+    )?;
+
+    let memory_type_index = context
+        .get_physical_device()
+        .find_memory_type(
+            mem_requirements.memory_type_bits,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+        .ok_or_else(|| {
+            VulkanError::AccelerationStructureCreationError(String::from(
+                "Cannot find a memory type",
+            ))
+        })?;
+
+    let alloc_info = vk::MemoryAllocateInfo::builder()
+        .allocation_size(mem_requirements.size)
+        .memory_type_index(memory_type_index)
+        .build();
+    let memory = device.allocate_memory(&alloc_info)?;
+
+    let bind_info = vk::BindAccelerationStructureMemoryInfoNV::builder()
+        .acceleration_structure(acceleration_structure)
+        .memory(memory)
+        .build();
+    if let Err(err) = device.bind_acceleration_structure_memory(&[bind_info]) {
+        device.free_memory(memory);
+        return Err(err);
+    }
+
+    Ok(memory)
+}
@@ -1,15 +1,19 @@
 use core::ptr;
+use std::cell::Cell;
+use std::mem;
 use std::os::raw::c_void;
-use std::rc::Rc;
 
 use ash::vk;
 
 use crate::device::VulkanDevice;
 use crate::errors::VulkanError;
+use crate::memory_allocator::{self, Allocation};
 use crate::vulkan_context::VulkanContext;
+use crate::Shared;
 
 pub enum BufferType {
     Index,
+    IndexOnly,
     RayTracing,
     RayTracingInstance,
     ShaderBindingTable,
@@ -17,19 +21,47 @@ pub enum BufferType {
     Storage,
     Uniform,
     Vertex,
+    VertexOnly,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum IndexType {
+    U16,
+    U32,
+}
+
+impl From<IndexType> for vk::IndexType {
+    fn from(index_type: IndexType) -> Self {
+        match index_type {
+            IndexType::U16 => vk::IndexType::UINT16,
+            IndexType::U32 => vk::IndexType::UINT32,
+        }
+    }
 }
 
 pub struct Buffer {
-    device: Rc<VulkanDevice>,
+    device: Shared<VulkanDevice>,
     buffer: vk::Buffer,
-    buffer_memory: vk::DeviceMemory,
+    allocation: Allocation,
     buffer_size: vk::DeviceSize,
+    memory_properties: vk::MemoryPropertyFlags,
+    usage: vk::BufferUsageFlags,
+    mapped: Cell<MappedPtr>,
 }
 
+/// Host address of the persistent mapping. The mapped memory is owned by the buffer, so the
+/// pointer can move to another thread along with it.
+#[derive(Copy, Clone)]
+struct MappedPtr(*mut c_void);
+
+unsafe impl Send for MappedPtr {}
+
 impl Drop for Buffer {
     fn drop(&mut self) {
+        if !self.mapped.get().0.is_null() {
+            self.device.unmap_memory(self.allocation.get_memory());
+        }
         self.device.destroy_buffer(self.buffer);
-        self.device.free_memory(self.buffer_memory);
     }
 }
 
@@ -39,21 +71,162 @@ impl Buffer {
     }
 
     pub fn get_memory(&self) -> vk::DeviceMemory {
-        self.buffer_memory
+        self.allocation.get_memory()
+    }
+
+    pub fn get_memory_offset(&self) -> vk::DeviceSize {
+        self.allocation.get_offset()
+    }
+
+    pub fn get_size(&self) -> vk::DeviceSize {
+        self.buffer_size
+    }
+
+    pub fn get_usage(&self) -> vk::BufferUsageFlags {
+        self.usage
+    }
+
+    /// Hands the buffer and its memory over to the caller, who becomes responsible for
+    /// destroying and freeing them. Suballocated buffers share their memory and cannot be
+    /// released.
+    pub fn into_raw(
+        mut self,
+    ) -> Result<(vk::Buffer, vk::DeviceMemory, vk::DeviceSize), VulkanError> {
+        if self.allocation.is_suballocated() {
+            return Err(VulkanError::BufferError(String::from(
+                "Cannot release a suballocated buffer",
+            )));
+        }
+
+        let mapped = self.mapped.replace(MappedPtr(ptr::null_mut())).0;
+        if !mapped.is_null() {
+            self.device.unmap_memory(self.allocation.get_memory());
+        }
+
+        let size = self.allocation.get_size();
+        let memory = self.allocation.release();
+        let buffer = mem::replace(&mut self.buffer, vk::Buffer::null());
+
+        Ok((buffer, memory, size))
     }
 
     pub fn copy_data(&self, buffer: *const c_void) -> Result<(), VulkanError> {
-        let data = self
-            .device
-            .map_memory(self.buffer_memory, self.buffer_size)?;
+        let persistent = self.mapped.get().0;
+        let data = if persistent.is_null() {
+            self.map_memory()?
+        } else {
+            persistent
+        };
         unsafe {
             ptr::copy(buffer, data, self.buffer_size as usize);
         }
-        self.device.unmap_memory(self.buffer_memory);
+        self.flush_if_non_coherent()?;
+        if persistent.is_null() {
+            self.device.unmap_memory(self.allocation.get_memory());
+        }
 
         Ok(())
     }
 
+    /// Maps the whole buffer once and keeps it mapped until the buffer is dropped.
+    pub fn map_persistent(&self) -> Result<*mut c_void, VulkanError> {
+        if !self
+            .memory_properties
+            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+        {
+            return Err(VulkanError::BufferError(String::from(
+                "Only host visible buffers can be mapped",
+            )));
+        }
+
+        if self.mapped.get().0.is_null() {
+            let data = self.map_memory()?;
+            self.mapped.set(MappedPtr(data));
+        }
+
+        Ok(self.mapped.get().0)
+    }
+
+    pub fn update(&self, data: &[u8], offset: vk::DeviceSize) -> Result<(), VulkanError> {
+        if offset + data.len() as vk::DeviceSize > self.buffer_size {
+            return Err(VulkanError::BufferError(format!(
+                "Update of {} bytes at offset {} overflows a buffer of {} bytes",
+                data.len(),
+                offset,
+                self.buffer_size
+            )));
+        }
+
+        let mapped = self.map_persistent()?;
+        unsafe {
+            ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                (mapped as *mut u8).add(offset as usize),
+                data.len(),
+            );
+        }
+
+        self.flush_if_non_coherent()
+    }
+
+    /// Makes device writes visible through the mapping; a no-op for coherent memory.
+    pub fn invalidate(&self) -> Result<(), VulkanError> {
+        if self.is_host_coherent() {
+            return Ok(());
+        }
+
+        self.device
+            .invalidate_mapped_memory_ranges(&[self.whole_range()])
+    }
+
+    fn flush_if_non_coherent(&self) -> Result<(), VulkanError> {
+        if self.is_host_coherent() {
+            return Ok(());
+        }
+
+        self.device
+            .flush_mapped_memory_ranges(&[self.whole_range()])
+    }
+
+    fn map_memory(&self) -> Result<*mut c_void, VulkanError> {
+        self.device.map_memory_range(
+            self.allocation.get_memory(),
+            self.allocation.get_offset(),
+            self.buffer_size,
+        )
+    }
+
+    fn is_host_coherent(&self) -> bool {
+        self.memory_properties
+            .contains(vk::MemoryPropertyFlags::HOST_COHERENT)
+    }
+
+    fn whole_range(&self) -> vk::MappedMemoryRange {
+        vk::MappedMemoryRange::builder()
+            .memory(self.allocation.get_memory())
+            .offset(self.allocation.get_offset())
+            .size(vk::WHOLE_SIZE)
+            .build()
+    }
+
+    #[cfg(feature = "external_memory")]
+    pub fn get_memory_fd(
+        &self,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+    ) -> Result<i32, VulkanError> {
+        self.device
+            .get_memory_fd(self.allocation.get_memory(), handle_type)
+    }
+
+    #[cfg(feature = "external_memory")]
+    pub fn get_memory_win32_handle(
+        &self,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+    ) -> Result<vk::HANDLE, VulkanError> {
+        self.device
+            .get_memory_win32_handle(self.allocation.get_memory(), handle_type)
+    }
+
     pub fn update_buffer(&self, command_buffer: vk::CommandBuffer, data: &[u8]) {
         self.device
             .cmd_update_buffer(command_buffer, self.buffer, data);
@@ -64,6 +237,14 @@ pub struct BufferBuilder<'a> {
     context: &'a VulkanContext,
     ty: BufferType,
     buffer_size: vk::DeviceSize,
+    alignment: vk::DeviceSize,
+    usage_flags: Option<vk::BufferUsageFlags>,
+    #[cfg(feature = "external_memory")]
+    external_memory_handle_types: vk::ExternalMemoryHandleTypeFlags,
+    #[cfg(feature = "external_memory")]
+    imported_fd: Option<i32>,
+    #[cfg(feature = "external_memory")]
+    imported_win32_handle: Option<vk::HANDLE>,
 }
 
 impl<'a> BufferBuilder<'a> {
@@ -72,6 +253,14 @@ impl<'a> BufferBuilder<'a> {
             context,
             ty: BufferType::Uniform,
             buffer_size: 0,
+            alignment: 1,
+            usage_flags: None,
+            #[cfg(feature = "external_memory")]
+            external_memory_handle_types: vk::ExternalMemoryHandleTypeFlags::empty(),
+            #[cfg(feature = "external_memory")]
+            imported_fd: None,
+            #[cfg(feature = "external_memory")]
+            imported_win32_handle: None,
         }
     }
 
@@ -85,6 +274,35 @@ impl<'a> BufferBuilder<'a> {
         self
     }
 
+    pub fn with_alignment(mut self, alignment: vk::DeviceSize) -> Self {
+        self.alignment = alignment.max(1);
+        self
+    }
+
+    /// Overrides the usage derived from the buffer type; memory properties still follow the type.
+    pub fn with_usage_flags(mut self, usage_flags: vk::BufferUsageFlags) -> Self {
+        self.usage_flags = Some(usage_flags);
+        self
+    }
+
+    #[cfg(feature = "external_memory")]
+    pub fn with_external_memory(mut self, handle_types: vk::ExternalMemoryHandleTypeFlags) -> Self {
+        self.external_memory_handle_types = handle_types;
+        self
+    }
+
+    #[cfg(feature = "external_memory")]
+    pub fn with_imported_fd(mut self, fd: i32) -> Self {
+        self.imported_fd = Some(fd);
+        self
+    }
+
+    #[cfg(feature = "external_memory")]
+    pub fn with_imported_win32_handle(mut self, handle: vk::HANDLE) -> Self {
+        self.imported_win32_handle = Some(handle);
+        self
+    }
+
     pub fn build(self) -> Result<Buffer, VulkanError> {
         let usage = match &self.ty {
             BufferType::Index => {
@@ -92,9 +310,14 @@ impl<'a> BufferBuilder<'a> {
                     | vk::BufferUsageFlags::TRANSFER_DST
                     | vk::BufferUsageFlags::STORAGE_BUFFER
             }
+            BufferType::IndexOnly => {
+                vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST
+            }
             BufferType::RayTracing => vk::BufferUsageFlags::RAY_TRACING_NV,
             BufferType::RayTracingInstance => vk::BufferUsageFlags::RAY_TRACING_NV,
-            BufferType::ShaderBindingTable => vk::BufferUsageFlags::TRANSFER_SRC,
+            BufferType::ShaderBindingTable => {
+                vk::BufferUsageFlags::RAY_TRACING_NV | vk::BufferUsageFlags::TRANSFER_SRC
+            }
             BufferType::Staging => vk::BufferUsageFlags::TRANSFER_SRC,
             BufferType::Storage => vk::BufferUsageFlags::STORAGE_BUFFER,
             BufferType::Uniform => {
@@ -105,10 +328,14 @@ impl<'a> BufferBuilder<'a> {
                     | vk::BufferUsageFlags::TRANSFER_DST
                     | vk::BufferUsageFlags::STORAGE_BUFFER
             }
+            BufferType::VertexOnly => {
+                vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST
+            }
         };
 
         let properties = match &self.ty {
             BufferType::Index => vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            BufferType::IndexOnly => vk::MemoryPropertyFlags::DEVICE_LOCAL,
             BufferType::RayTracing => vk::MemoryPropertyFlags::DEVICE_LOCAL,
             BufferType::RayTracingInstance => {
                 vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
@@ -124,13 +351,27 @@ impl<'a> BufferBuilder<'a> {
                 vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
             }
             BufferType::Vertex => vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            BufferType::VertexOnly => vk::MemoryPropertyFlags::DEVICE_LOCAL,
         };
 
+        let usage = self.usage_flags.unwrap_or(usage);
         let buffer_info = vk::BufferCreateInfo::builder()
             .size(self.buffer_size)
             .usage(usage)
-            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+        #[cfg(feature = "external_memory")]
+        let mut external_buffer_info = vk::ExternalMemoryBufferCreateInfo::builder()
+            .handle_types(self.external_memory_handle_types)
             .build();
+        #[cfg(feature = "external_memory")]
+        let buffer_info = if self.external_memory_handle_types.is_empty() {
+            buffer_info
+        } else {
+            buffer_info.push_next(&mut external_buffer_info)
+        };
+
+        let buffer_info = buffer_info.build();
 
         let buffer = self.context.get_device().create_buffer(&buffer_info)?;
 
@@ -139,29 +380,122 @@ impl<'a> BufferBuilder<'a> {
             .get_device()
             .get_buffer_memory_requirements(buffer);
 
-        let memory_type_index = self
-            .context
-            .get_physical_device()
+        let (memory_type_index, memory_properties) = self
             .find_memory_type(mem_requirements.memory_type_bits, properties)
             .ok_or_else(|| {
                 VulkanError::VertexBufferCreationError(String::from("Cannot find a memory type"))
             })?;
 
-        let alloc_info = vk::MemoryAllocateInfo::builder()
-            .allocation_size(mem_requirements.size)
-            .memory_type_index(memory_type_index)
-            .build();
+        let alignment = self.alignment.max(mem_requirements.alignment);
+        let allocation_size = mem_requirements.size.div_ceil(alignment) * alignment;
 
-        let buffer_memory = self.context.get_device().allocate_memory(&alloc_info)?;
-        self.context
-            .get_device()
-            .bind_buffer_memory(buffer, buffer_memory)?;
+        let requirements = vk::MemoryRequirements {
+            size: allocation_size,
+            alignment,
+            memory_type_bits: mem_requirements.memory_type_bits,
+        };
+
+        #[cfg(feature = "external_memory")]
+        let allocation = if self.uses_external_memory() {
+            let memory = self.allocate_external_memory(allocation_size, memory_type_index)?;
+            Allocation::dedicated(self.context.get_device(), memory, allocation_size)
+        } else {
+            memory_allocator::allocate(
+                self.context,
+                requirements,
+                memory_type_index,
+                memory_properties,
+            )?
+        };
+        #[cfg(not(feature = "external_memory"))]
+        let allocation = memory_allocator::allocate(
+            self.context,
+            requirements,
+            memory_type_index,
+            memory_properties,
+        )?;
+
+        self.context.get_device().bind_buffer_memory(
+            buffer,
+            allocation.get_memory(),
+            allocation.get_offset(),
+        )?;
 
         Ok(Buffer {
-            device: Rc::clone(self.context.get_device()),
+            device: Shared::clone(self.context.get_device()),
             buffer,
-            buffer_memory,
+            allocation,
             buffer_size: self.buffer_size,
+            memory_properties,
+            usage,
+            mapped: Cell::new(MappedPtr(ptr::null_mut())),
         })
     }
+
+    #[cfg(feature = "external_memory")]
+    fn uses_external_memory(&self) -> bool {
+        !self.external_memory_handle_types.is_empty()
+            || self.imported_fd.is_some()
+            || self.imported_win32_handle.is_some()
+    }
+
+    #[cfg(feature = "external_memory")]
+    fn allocate_external_memory(
+        &self,
+        allocation_size: vk::DeviceSize,
+        memory_type_index: u32,
+    ) -> Result<vk::DeviceMemory, VulkanError> {
+        let alloc_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(allocation_size)
+            .memory_type_index(memory_type_index);
+
+        let mut export_info = vk::ExportMemoryAllocateInfo::builder()
+            .handle_types(self.external_memory_handle_types)
+            .build();
+        let mut import_fd_info = vk::ImportMemoryFdInfoKHR::builder()
+            .handle_type(self.external_memory_handle_types)
+            .fd(self.imported_fd.unwrap_or(-1))
+            .build();
+        let mut import_win32_info = vk::ImportMemoryWin32HandleInfoKHR::builder()
+            .handle_type(self.external_memory_handle_types)
+            .handle(self.imported_win32_handle.unwrap_or_else(ptr::null_mut))
+            .build();
+        let alloc_info = if self.imported_fd.is_some() {
+            alloc_info.push_next(&mut import_fd_info)
+        } else if self.imported_win32_handle.is_some() {
+            alloc_info.push_next(&mut import_win32_info)
+        } else if !self.external_memory_handle_types.is_empty() {
+            alloc_info.push_next(&mut export_info)
+        } else {
+            alloc_info
+        };
+
+        let alloc_info = alloc_info.build();
+
+        self.context.get_device().allocate_memory(&alloc_info)
+    }
+
+    fn find_memory_type(
+        &self,
+        type_filter: u32,
+        properties: vk::MemoryPropertyFlags,
+    ) -> Option<(u32, vk::MemoryPropertyFlags)> {
+        let physical_device = self.context.get_physical_device();
+
+        physical_device
+            .find_memory_type(type_filter, properties)
+            .map(|index| (index, properties))
+            .or_else(|| {
+                let host_coherent =
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+                if properties.contains(host_coherent) {
+                    let fallback = properties & !vk::MemoryPropertyFlags::HOST_COHERENT;
+                    physical_device
+                        .find_memory_type(type_filter, fallback)
+                        .map(|index| (index, fallback))
+                } else {
+                    None
+                }
+            })
+    }
 }
@@ -0,0 +1,106 @@
+use ash::vk;
+
+use crate::device::VulkanDevice;
+use crate::errors::VulkanError;
+use crate::image;
+use crate::memory_allocator::Allocation;
+use crate::vulkan_context::VulkanContext;
+use crate::Shared;
+
+pub struct ColorResources {
+    device: Shared<VulkanDevice>,
+    color_format: vk::Format,
+    color_image: vk::Image,
+    _color_image_memory: Allocation,
+    color_image_view: vk::ImageView,
+}
+
+impl Drop for ColorResources {
+    fn drop(&mut self) {
+        self.device.destroy_image_view(self.color_image_view);
+        self.device.destroy_image(self.color_image);
+    }
+}
+
+impl ColorResources {
+    pub fn get_format(&self) -> vk::Format {
+        self.color_format
+    }
+
+    pub fn get_image(&self) -> vk::Image {
+        self.color_image
+    }
+
+    pub fn get_image_view(&self) -> vk::ImageView {
+        self.color_image_view
+    }
+}
+
+pub struct ColorResourcesBuilder<'a> {
+    context: &'a VulkanContext,
+    width: u32,
+    height: u32,
+    format: vk::Format,
+    samples: vk::SampleCountFlags,
+}
+
+impl<'a> ColorResourcesBuilder<'a> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        ColorResourcesBuilder {
+            context,
+            width: 0,
+            height: 0,
+            format: vk::Format::B8G8R8A8_UNORM,
+            samples: vk::SampleCountFlags::TYPE_1,
+        }
+    }
+
+    pub fn with_width(mut self, width: u32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_height(mut self, height: u32) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub fn with_format(mut self, format: vk::Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn build(self) -> Result<ColorResources, VulkanError> {
+        let (color_image, color_image_memory) = image::ImageBuilder::new(self.context)
+            .with_width(self.width)
+            .with_height(self.height)
+            .with_format(self.format)
+            .with_tiling(vk::ImageTiling::OPTIMAL)
+            .with_usage(
+                vk::ImageUsageFlags::TRANSIENT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            )
+            .with_properties(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            .with_samples(self.samples)
+            .build()?;
+
+        let color_image_view = image::create_image_view(
+            self.context,
+            color_image,
+            self.format,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+
+        Ok(ColorResources {
+            device: Shared::clone(self.context.get_device()),
+            color_format: self.format,
+            color_image,
+            _color_image_memory: color_image_memory,
+            color_image_view,
+        })
+    }
+}
@@ -1,15 +1,18 @@
-use std::rc::Rc;
-
 use ash::vk;
 
 use crate::device::VulkanDevice;
 use crate::errors::VulkanError;
 use crate::physical_device::PhysicalDevice;
+use crate::Shared;
+
+const FENCE_TIMEOUT: u64 = u64::MAX;
 
 pub struct CommandBuffers {
-    device: Rc<VulkanDevice>,
+    device: Shared<VulkanDevice>,
     command_pool: vk::CommandPool,
     command_buffers: Vec<vk::CommandBuffer>,
+    extra_command_buffers: Vec<vk::CommandBuffer>,
+    extra_command_buffers_per_frame: usize,
     fences: Vec<vk::Fence>,
     present_complete_semaphores: Vec<vk::Semaphore>,
     render_complete_semaphores: Vec<vk::Semaphore>,
@@ -26,6 +29,10 @@ impl Drop for CommandBuffers {
         for fence in self.fences.iter() {
             self.device.destroy_fence(*fence);
         }
+        if !self.extra_command_buffers.is_empty() {
+            self.device
+                .free_command_buffers(self.command_pool, &self.extra_command_buffers);
+        }
         self.device
             .free_command_buffers(self.command_pool, &self.command_buffers);
         self.device.destroy_command_pool(self.command_pool);
@@ -37,6 +44,24 @@ impl CommandBuffers {
         self.command_buffers[index]
     }
 
+    pub fn get_extra_command_buffer(&self, frame_index: usize, slot: usize) -> vk::CommandBuffer {
+        self.extra_command_buffers[frame_index * self.extra_command_buffers_per_frame + slot]
+    }
+
+    fn frame_command_buffers(&self, frame_index: usize) -> Vec<vk::CommandBuffer> {
+        let first_extra = frame_index * self.extra_command_buffers_per_frame;
+        let mut command_buffers = vec![self.command_buffers[frame_index]];
+        command_buffers.extend_from_slice(
+            &self.extra_command_buffers
+                [first_extra..first_extra + self.extra_command_buffers_per_frame],
+        );
+        command_buffers
+    }
+
+    pub fn pool(&self) -> vk::CommandPool {
+        self.command_pool
+    }
+
     pub fn get_present_complete_semaphore(&self, index: usize) -> vk::Semaphore {
         self.present_complete_semaphores[index]
     }
@@ -82,33 +107,105 @@ impl CommandBuffers {
         Ok(())
     }
 
+    pub fn end_single_time_commands_async(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        signal_semaphore: vk::Semaphore,
+        fence: vk::Fence,
+    ) -> Result<(), VulkanError> {
+        self.device.end_command_buffer(command_buffer)?;
+
+        let command_buffers = [command_buffer];
+        let signal_semaphores = [signal_semaphore];
+        let submit_info = vk::SubmitInfo::builder()
+            .command_buffers(&command_buffers)
+            .signal_semaphores(&signal_semaphores)
+            .build();
+
+        self.device.queue_submit(&[submit_info], fence)
+    }
+
+    pub fn end_single_time_commands_with_fence(
+        &self,
+        command_buffer: vk::CommandBuffer,
+    ) -> Result<vk::Fence, VulkanError> {
+        self.device.end_command_buffer(command_buffer)?;
+
+        let fence_info = vk::FenceCreateInfo::builder().build();
+        let fence = self.device.create_fence(&fence_info)?;
+
+        let command_buffers = [command_buffer];
+        let submit_info = vk::SubmitInfo::builder()
+            .command_buffers(&command_buffers)
+            .build();
+
+        if let Err(err) = self.device.queue_submit(&[submit_info], fence) {
+            self.device.destroy_fence(fence);
+            return Err(err);
+        }
+
+        Ok(fence)
+    }
+
+    pub fn free_single_time_commands(&self, command_buffer: vk::CommandBuffer) {
+        self.device
+            .free_command_buffers(self.command_pool, &[command_buffer]);
+    }
+
     pub fn wait_for_fence(&self, frame_index: usize) -> Result<(), VulkanError> {
-        self.device.wait_for_fences(&[self.fences[frame_index]])
+        self.device
+            .wait_for_fences(&[self.fences[frame_index]], FENCE_TIMEOUT)?;
+        Ok(())
+    }
+
+    pub fn wait_all_fences_timeout(&self, timeout_ns: u64) -> Result<bool, VulkanError> {
+        self.device.wait_for_fences(&self.fences, timeout_ns)
+    }
+
+    pub fn is_frame_complete(&self, frame_index: usize) -> Result<bool, VulkanError> {
+        self.device.get_fence_status(self.fences[frame_index])
     }
 
     pub fn reset_fence(&self, frame_index: usize) -> Result<(), VulkanError> {
-        self.device.reset_fences(&[self.fences[frame_index]])
+        self.reset_fences(&[frame_index])
+    }
+
+    pub fn reset_fences(&self, frame_indices: &[usize]) -> Result<(), VulkanError> {
+        let fences: Vec<vk::Fence> = frame_indices
+            .iter()
+            .map(|frame_index| self.fences[*frame_index])
+            .collect();
+        self.device.reset_fences(&fences)
     }
 
     pub fn begin_command_buffer(&self, frame_index: usize) -> Result<(), VulkanError> {
         let begin_info = vk::CommandBufferBeginInfo::builder()
             .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
             .build();
-        self.device
-            .begin_command_buffer(self.command_buffers[frame_index], &begin_info)
+        for command_buffer in self.frame_command_buffers(frame_index) {
+            self.device
+                .begin_command_buffer(command_buffer, &begin_info)?;
+        }
+        Ok(())
     }
 
     pub fn end_command_buffer(&self, frame_index: usize) -> Result<(), VulkanError> {
-        self.device
-            .end_command_buffer(self.command_buffers[frame_index])
+        for command_buffer in self.frame_command_buffers(frame_index) {
+            self.device.end_command_buffer(command_buffer)?;
+        }
+        Ok(())
     }
 
     pub fn queue_submit(&self, frame_index: usize) -> Result<(), VulkanError> {
+        let wait_semaphores = [self.present_complete_semaphores[frame_index]];
+        let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+        let command_buffers = self.frame_command_buffers(frame_index);
+        let signal_semaphores = [self.render_complete_semaphores[frame_index]];
         let info = vk::SubmitInfo::builder()
-            .wait_semaphores(&[self.present_complete_semaphores[frame_index]])
-            .wait_dst_stage_mask(&[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT])
-            .command_buffers(&[self.command_buffers[frame_index]])
-            .signal_semaphores(&[self.render_complete_semaphores[frame_index]])
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .command_buffers(&command_buffers)
+            .signal_semaphores(&signal_semaphores)
             .build();
 
         self.device.queue_submit(&[info], self.fences[frame_index])
@@ -117,16 +214,18 @@ impl CommandBuffers {
 
 pub struct CommandBuffersBuilder<'a> {
     physical_device: &'a PhysicalDevice,
-    device: Rc<VulkanDevice>,
+    device: Shared<VulkanDevice>,
     frames_count: u32,
+    command_buffers_per_frame: u32,
 }
 
 impl<'a> CommandBuffersBuilder<'a> {
-    pub fn new(physical_device: &'a PhysicalDevice, device: Rc<VulkanDevice>) -> Self {
+    pub fn new(physical_device: &'a PhysicalDevice, device: Shared<VulkanDevice>) -> Self {
         Self {
             physical_device,
             device,
             frames_count: 1,
+            command_buffers_per_frame: 1,
         }
     }
 
@@ -135,6 +234,11 @@ impl<'a> CommandBuffersBuilder<'a> {
         self
     }
 
+    pub fn with_command_buffers_per_frame(mut self, command_buffers_per_frame: u32) -> Self {
+        self.command_buffers_per_frame = command_buffers_per_frame.max(1);
+        self
+    }
+
     pub fn build(self) -> Result<CommandBuffers, VulkanError> {
         let mut fences = vec![];
         let mut present_complete_semaphores = vec![];
@@ -142,7 +246,7 @@ impl<'a> CommandBuffersBuilder<'a> {
 
         let pool_info = vk::CommandPoolCreateInfo::builder()
             .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
-            .queue_family_index(self.physical_device.get_queue_family())
+            .queue_family_index(self.physical_device.get_graphics_queue_family())
             .build();
         let command_pool = self.device.create_command_pool(&pool_info)?;
 
@@ -154,6 +258,18 @@ impl<'a> CommandBuffersBuilder<'a> {
 
         let command_buffers = self.device.allocate_command_buffers(&alloc_info)?;
 
+        let extra_command_buffers_per_frame = self.command_buffers_per_frame - 1;
+        let extra_command_buffers = if extra_command_buffers_per_frame > 0 {
+            let alloc_info = vk::CommandBufferAllocateInfo::builder()
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_pool(command_pool)
+                .command_buffer_count(self.frames_count * extra_command_buffers_per_frame)
+                .build();
+            self.device.allocate_command_buffers(&alloc_info)?
+        } else {
+            vec![]
+        };
+
         for _ in 0..self.frames_count {
             let fence_info = vk::FenceCreateInfo::builder()
                 .flags(vk::FenceCreateFlags::SIGNALED)
@@ -164,10 +280,13 @@ impl<'a> CommandBuffersBuilder<'a> {
             present_complete_semaphores.push(self.device.create_semaphore(&semaphore_info)?);
             render_complete_semaphores.push(self.device.create_semaphore(&semaphore_info)?);
         }
+
         Ok(CommandBuffers {
             device: self.device,
             command_pool,
             command_buffers,
+            extra_command_buffers,
+            extra_command_buffers_per_frame: extra_command_buffers_per_frame as usize,
             fences,
             present_complete_semaphores,
             render_complete_semaphores,
@@ -1,9 +1,18 @@
 use ash::vk;
 
+/// Receives every message the debug messenger lets through, instead of the `log` crate.
+#[cfg(not(feature = "multithread"))]
+pub type DebugCallback = Box<dyn Fn(DebugSeverity, DebugType, &str)>;
+#[cfg(feature = "multithread")]
+pub type DebugCallback = Box<dyn Fn(DebugSeverity, DebugType, &str) + Send + Sync>;
+
 #[derive(Default, Copy, Clone)]
 pub struct DebugOptions {
     pub debug_severity: DebugSeverity,
     pub debug_type: DebugType,
+    /// Instruments shaders to catch out of bounds descriptor indexing and similar GPU-side errors.
+    pub gpu_assisted: bool,
+    pub best_practices: bool,
 }
 
 #[derive(Default, Copy, Clone, PartialEq)]
@@ -29,6 +38,38 @@ impl DebugSeverity {
     }
 }
 
+/// The default output of the debug messenger, for callbacks that only want to observe messages.
+pub fn log_message(severity: DebugSeverity, ty: DebugType, message: &str) {
+    let message = if ty.general {
+        format!("General Layer: {:?}", message)
+    } else if ty.validation {
+        format!("Validation layer: {:?}", message)
+    } else {
+        format!("Performance Layer: {:?}", message)
+    };
+
+    if severity.verbose {
+        log::trace!("{}", message);
+    } else if severity.info {
+        log::info!("{}", message);
+    } else if severity.warning {
+        log::warn!("{}", message);
+    } else if severity.error {
+        log::error!("{}", message);
+    }
+}
+
+impl From<vk::DebugUtilsMessageSeverityFlagsEXT> for DebugSeverity {
+    fn from(flags: vk::DebugUtilsMessageSeverityFlagsEXT) -> Self {
+        DebugSeverity {
+            verbose: flags.contains(vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE),
+            info: flags.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO),
+            warning: flags.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING),
+            error: flags.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR),
+        }
+    }
+}
+
 impl Into<vk::DebugUtilsMessageSeverityFlagsEXT> for DebugSeverity {
     fn into(self) -> vk::DebugUtilsMessageSeverityFlagsEXT {
         let mut message_severity = vk::DebugUtilsMessageSeverityFlagsEXT::empty();
@@ -69,6 +110,16 @@ impl DebugType {
     }
 }
 
+impl From<vk::DebugUtilsMessageTypeFlagsEXT> for DebugType {
+    fn from(flags: vk::DebugUtilsMessageTypeFlagsEXT) -> Self {
+        DebugType {
+            general: flags.contains(vk::DebugUtilsMessageTypeFlagsEXT::GENERAL),
+            validation: flags.contains(vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION),
+            performance: flags.contains(vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE),
+        }
+    }
+}
+
 impl Into<vk::DebugUtilsMessageTypeFlagsEXT> for DebugType {
     fn into(self) -> vk::DebugUtilsMessageTypeFlagsEXT {
         let mut message_type = vk::DebugUtilsMessageTypeFlagsEXT::empty();
@@ -1,25 +1,37 @@
-use std::rc::Rc;
-
 use ash::vk;
 
 use crate::device::VulkanDevice;
 use crate::errors::VulkanError;
+use crate::extensions::DeviceExtensions;
 use crate::image;
+use crate::memory_allocator::Allocation;
+use crate::sampler::{Sampler, SamplerKey};
 use crate::vulkan_context::VulkanContext;
+use crate::Shared;
 
 pub struct DepthResources {
-    device: Rc<VulkanDevice>,
+    device: Shared<VulkanDevice>,
     depth_format: vk::Format,
     depth_image: vk::Image,
-    depth_image_memory: vk::DeviceMemory,
+    _depth_image_memory: Allocation,
     depth_image_view: vk::ImageView,
+    layers: u32,
+    mip_levels: u32,
+    mip_views: Vec<vk::ImageView>,
+    pyramid_view: Option<vk::ImageView>,
+    pyramid_sampler: Option<Shared<Sampler>>,
 }
 
 impl Drop for DepthResources {
     fn drop(&mut self) {
+        if let Some(pyramid_view) = self.pyramid_view {
+            self.device.destroy_image_view(pyramid_view);
+        }
+        for mip_view in self.mip_views.iter() {
+            self.device.destroy_image_view(*mip_view);
+        }
         self.device.destroy_image_view(self.depth_image_view);
         self.device.destroy_image(self.depth_image);
-        self.device.free_memory(self.depth_image_memory);
     }
 }
 
@@ -28,15 +40,48 @@ impl DepthResources {
         self.depth_format
     }
 
+    pub fn get_image(&self) -> vk::Image {
+        self.depth_image
+    }
+
     pub fn get_image_view(&self) -> vk::ImageView {
         self.depth_image_view
     }
+
+    pub fn get_layers(&self) -> u32 {
+        self.layers
+    }
+
+    pub fn get_mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    /// Single level view, to render a pyramid level from the one above it.
+    pub fn get_mip_view(&self, level: u32) -> vk::ImageView {
+        self.mip_views[level as usize]
+    }
+
+    /// View over every mip level, to be sampled with `get_pyramid_sampler`.
+    pub fn get_pyramid_view(&self) -> Option<vk::ImageView> {
+        self.pyramid_view
+    }
+
+    pub fn get_pyramid_sampler(&self) -> Option<&Shared<Sampler>> {
+        self.pyramid_sampler.as_ref()
+    }
 }
 
 pub struct DepthResourcesBuilder<'a> {
     context: &'a VulkanContext,
     width: u32,
     height: u32,
+    sampled: bool,
+    samples: vk::SampleCountFlags,
+    low_precision: bool,
+    layers: u32,
+    mipmaps: bool,
+    lod_range: Option<(u32, u32)>,
+    reduction_mode: vk::SamplerReductionModeEXT,
 }
 
 impl<'a> DepthResourcesBuilder<'a> {
@@ -45,6 +90,13 @@ impl<'a> DepthResourcesBuilder<'a> {
             context,
             width: 0,
             height: 0,
+            sampled: false,
+            samples: vk::SampleCountFlags::TYPE_1,
+            low_precision: false,
+            layers: 1,
+            mipmaps: false,
+            lod_range: None,
+            reduction_mode: vk::SamplerReductionModeEXT::MAX,
         }
     }
 
@@ -58,43 +110,196 @@ impl<'a> DepthResourcesBuilder<'a> {
         self
     }
 
+    pub fn with_sampled(mut self, sampled: bool) -> Self {
+        self.sampled = sampled;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn with_low_precision(mut self, low_precision: bool) -> Self {
+        self.low_precision = low_precision;
+        self
+    }
+
+    /// Makes the depth image an array with one layer per view, for multiview render passes.
+    pub fn with_layers(mut self, layers: u32) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Gives the depth image a full mip chain for hierarchical-Z culling, along with a sampler
+    /// that reduces each 2x2 footprint with `with_reduction_mode`. Requires
+    /// VK_EXT_sampler_filter_minmax.
+    pub fn with_mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    /// Mip levels the pyramid sampler is clamped to, every level by default.
+    pub fn with_lod_range(mut self, min_lod: u32, max_lod: u32) -> Self {
+        self.lod_range = Some((min_lod, max_lod));
+        self
+    }
+
+    /// `MAX` by default; use `MIN` with a reversed depth range.
+    pub fn with_reduction_mode(mut self, reduction_mode: vk::SamplerReductionModeEXT) -> Self {
+        self.reduction_mode = reduction_mode;
+        self
+    }
+
     pub fn build(self) -> Result<DepthResources, VulkanError> {
-        let depth_format = self.choose_supported_format(
-            vk::ImageTiling::OPTIMAL,
-            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
-        );
+        if self.layers == 0 {
+            return Err(VulkanError::DepthResourcesCreationError(String::from(
+                "A depth image needs at least one layer",
+            )));
+        }
+        if self.mipmaps && self.samples != vk::SampleCountFlags::TYPE_1 {
+            return Err(VulkanError::DepthResourcesCreationError(String::from(
+                "Multisampled depth images cannot be mipmapped",
+            )));
+        }
+        if self.layers > 1 && self.mipmaps {
+            return Err(VulkanError::DepthResourcesCreationError(String::from(
+                "Layered depth images cannot be mipmapped",
+            )));
+        }
 
-        let (depth_image, depth_image_memory) = image::create_image(
-            self.context,
-            self.width,
-            self.height,
-            depth_format,
-            vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-        )?;
+        if self.mipmaps
+            && !self
+                .context
+                .get_device()
+                .is_extension_enabled(DeviceExtensions::ExtSamplerFilterMinmax)
+        {
+            return Err(VulkanError::ImageCreationError(String::from(
+                "Mipmapped depth requires VK_EXT_sampler_filter_minmax",
+            )));
+        }
 
-        let depth_image_view = image::create_image_view(
-            self.context,
-            depth_image,
-            depth_format,
-            vk::ImageAspectFlags::DEPTH,
-        )?;
+        let mip_levels = if self.mipmaps {
+            32 - self.width.max(self.height).max(1).leading_zeros()
+        } else {
+            1
+        };
+
+        let (min_lod, max_lod) = self.lod_range.unwrap_or((0, mip_levels - 1));
+        if min_lod > max_lod || max_lod >= mip_levels {
+            return Err(VulkanError::DepthResourcesCreationError(format!(
+                "LOD range {}..={} is outside of the {} mip levels",
+                min_lod, max_lod, mip_levels
+            )));
+        }
+
+        let sampled = self.sampled || self.mipmaps;
+
+        // Falls back to the next candidate when a format, e.g. D16, cannot be sampled.
+        let format_features = if sampled {
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT | vk::FormatFeatureFlags::SAMPLED_IMAGE
+        } else {
+            vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+        };
+        let depth_format = self.choose_supported_format(vk::ImageTiling::OPTIMAL, format_features);
+        if depth_format == vk::Format::UNDEFINED {
+            return Err(VulkanError::DepthResourcesCreationError(format!(
+                "No depth format supports {:?}",
+                format_features
+            )));
+        }
 
-        image::transition_image_layout(
+        let usage = if sampled {
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED
+        } else {
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+        };
+
+        let (depth_image, depth_image_memory) = image::ImageBuilder::new(self.context)
+            .with_width(self.width)
+            .with_height(self.height)
+            .with_format(depth_format)
+            .with_tiling(vk::ImageTiling::OPTIMAL)
+            .with_usage(usage)
+            .with_properties(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            .with_samples(self.samples)
+            .with_mip_levels(mip_levels)
+            .with_array_layers(self.layers)
+            .build()?;
+
+        let depth_image_view = if self.layers > 1 {
+            image::create_image_view_array(
+                self.context,
+                depth_image,
+                depth_format,
+                vk::ImageAspectFlags::DEPTH,
+                self.layers,
+            )?
+        } else {
+            image::create_image_view(
+                self.context,
+                depth_image,
+                depth_format,
+                vk::ImageAspectFlags::DEPTH,
+            )?
+        };
+
+        let mut mip_views = vec![];
+        let mut pyramid_view = None;
+        let mut pyramid_sampler = None;
+        if self.mipmaps {
+            for level in 0..mip_levels {
+                mip_views.push(image::create_image_view_mip_range(
+                    self.context,
+                    depth_image,
+                    depth_format,
+                    vk::ImageAspectFlags::DEPTH,
+                    level,
+                    1,
+                )?);
+            }
+            pyramid_view = Some(image::create_image_view_mip_range(
+                self.context,
+                depth_image,
+                depth_format,
+                vk::ImageAspectFlags::DEPTH,
+                0,
+                mip_levels,
+            )?);
+            pyramid_sampler = Some(self.context.get_sampler_cache().get_or_create(SamplerKey {
+                filter: vk::Filter::LINEAR,
+                mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+                address_mode: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+                reduction_mode: Some(self.reduction_mode),
+                min_lod,
+                max_lod,
+            })?);
+        }
+
+        image::transition_image_layout_range(
             self.context,
             depth_image,
             depth_format,
             vk::ImageLayout::UNDEFINED,
             vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            image::TransitionRange {
+                level_count: mip_levels,
+                layer_count: self.layers,
+                ..Default::default()
+            },
         )?;
 
         Ok(DepthResources {
-            device: Rc::clone(self.context.get_device()),
+            device: Shared::clone(self.context.get_device()),
             depth_format,
             depth_image,
-            depth_image_memory,
+            _depth_image_memory: depth_image_memory,
             depth_image_view,
+            layers: self.layers,
+            mip_levels,
+            mip_views,
+            pyramid_view,
+            pyramid_sampler,
         })
     }
 
@@ -103,11 +308,15 @@ impl<'a> DepthResourcesBuilder<'a> {
         tiling: vk::ImageTiling,
         features: vk::FormatFeatureFlags,
     ) -> vk::Format {
-        let candidates = vec![
+        let mut candidates = vec![
             vk::Format::D32_SFLOAT_S8_UINT,
             vk::Format::D24_UNORM_S8_UINT,
         ];
 
+        if self.low_precision {
+            candidates.insert(0, vk::Format::D16_UNORM);
+        }
+
         candidates
             .into_iter()
             .find(|format| {
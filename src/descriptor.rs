@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+
+use ash::vk;
+
+use crate::buffer::Buffer;
+use crate::device::VulkanDevice;
+use crate::errors::VulkanError;
+use crate::texture::Texture;
+use crate::vulkan_context::VulkanContext;
+use crate::Shared;
+
+pub struct DescriptorSetLayout {
+    device: Shared<VulkanDevice>,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    bindings: Vec<vk::DescriptorSetLayoutBinding>,
+}
+
+impl Drop for DescriptorSetLayout {
+    fn drop(&mut self) {
+        self.device
+            .destroy_descriptor_set_layout(self.descriptor_set_layout);
+    }
+}
+
+impl DescriptorSetLayout {
+    pub fn get(&self) -> vk::DescriptorSetLayout {
+        self.descriptor_set_layout
+    }
+
+    pub fn get_bindings(&self) -> &[vk::DescriptorSetLayoutBinding] {
+        &self.bindings
+    }
+}
+
+pub struct DescriptorSetLayoutBuilder<'a> {
+    context: &'a VulkanContext,
+    bindings: Vec<vk::DescriptorSetLayoutBinding>,
+    immutable_samplers: Vec<Vec<vk::Sampler>>,
+}
+
+impl<'a> DescriptorSetLayoutBuilder<'a> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        DescriptorSetLayoutBuilder {
+            context,
+            bindings: vec![],
+            immutable_samplers: vec![],
+        }
+    }
+
+    pub fn with_binding(
+        mut self,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        descriptor_count: u32,
+        stage_flags: vk::ShaderStageFlags,
+    ) -> Self {
+        self.bindings.push(
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(descriptor_type)
+                .descriptor_count(descriptor_count)
+                .stage_flags(stage_flags)
+                .build(),
+        );
+        self.immutable_samplers.push(vec![]);
+        self
+    }
+
+    pub fn with_immutable_samplers(
+        mut self,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        stage_flags: vk::ShaderStageFlags,
+        samplers: &[vk::Sampler],
+    ) -> Self {
+        self.bindings.push(
+            vk::DescriptorSetLayoutBinding::builder()
+                .binding(binding)
+                .descriptor_type(descriptor_type)
+                .descriptor_count(samplers.len() as u32)
+                .stage_flags(stage_flags)
+                .build(),
+        );
+        self.immutable_samplers.push(samplers.to_vec());
+        self
+    }
+
+    pub fn build(self) -> Result<DescriptorSetLayout, VulkanError> {
+        let has_invalid_sampler_binding = self
+            .bindings
+            .iter()
+            .zip(self.immutable_samplers.iter())
+            .any(|(binding, samplers)| {
+                !samplers.is_empty()
+                    && binding.descriptor_type != vk::DescriptorType::SAMPLER
+                    && binding.descriptor_type != vk::DescriptorType::COMBINED_IMAGE_SAMPLER
+            });
+
+        if has_invalid_sampler_binding {
+            return Err(VulkanError::PipelineError(String::from(
+                "Immutable samplers require a SAMPLER or COMBINED_IMAGE_SAMPLER binding",
+            )));
+        }
+
+        let layout_bindings: Vec<vk::DescriptorSetLayoutBinding> = self
+            .bindings
+            .iter()
+            .zip(self.immutable_samplers.iter())
+            .map(|(binding, samplers)| {
+                let mut binding = *binding;
+                if !samplers.is_empty() {
+                    binding.p_immutable_samplers = samplers.as_ptr();
+                }
+                binding
+            })
+            .collect();
+
+        let layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .bindings(&layout_bindings)
+            .build();
+
+        let descriptor_set_layout = self
+            .context
+            .get_device()
+            .create_descriptor_set_layout(&layout_info)?;
+
+        Ok(DescriptorSetLayout {
+            device: Shared::clone(self.context.get_device()),
+            descriptor_set_layout,
+            bindings: self.bindings,
+        })
+    }
+}
+
+pub struct DescriptorPool {
+    device: Shared<VulkanDevice>,
+    descriptor_pool: vk::DescriptorPool,
+}
+
+impl Drop for DescriptorPool {
+    fn drop(&mut self) {
+        self.device.destroy_descriptor_pool(self.descriptor_pool);
+    }
+}
+
+impl DescriptorPool {
+    pub fn get(&self) -> vk::DescriptorPool {
+        self.descriptor_pool
+    }
+
+    pub fn allocate(&self, layout: &DescriptorSetLayout) -> Result<vk::DescriptorSet, VulkanError> {
+        let set_layouts = [layout.get()];
+        let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.descriptor_pool)
+            .set_layouts(&set_layouts)
+            .build();
+
+        Ok(self.device.allocate_descriptor_sets(&alloc_info)?[0])
+    }
+}
+
+pub struct DescriptorPoolBuilder<'a> {
+    context: &'a VulkanContext,
+    layouts: Vec<(&'a DescriptorSetLayout, u32)>,
+}
+
+impl<'a> DescriptorPoolBuilder<'a> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        DescriptorPoolBuilder {
+            context,
+            layouts: vec![],
+        }
+    }
+
+    pub fn with_layout(mut self, layout: &'a DescriptorSetLayout, sets_count: u32) -> Self {
+        self.layouts.push((layout, sets_count));
+        self
+    }
+
+    pub fn build(self) -> Result<DescriptorPool, VulkanError> {
+        let mut descriptor_counts: HashMap<vk::DescriptorType, u32> = HashMap::new();
+        let mut max_sets = 0;
+
+        for (layout, sets_count) in self.layouts.iter() {
+            for binding in layout.get_bindings() {
+                *descriptor_counts
+                    .entry(binding.descriptor_type)
+                    .or_insert(0) += binding.descriptor_count * sets_count;
+            }
+            max_sets += sets_count;
+        }
+
+        let pool_sizes: Vec<vk::DescriptorPoolSize> = descriptor_counts
+            .into_iter()
+            .map(|(ty, descriptor_count)| {
+                vk::DescriptorPoolSize::builder()
+                    .ty(ty)
+                    .descriptor_count(descriptor_count)
+                    .build()
+            })
+            .collect();
+
+        let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&pool_sizes)
+            .max_sets(max_sets)
+            .build();
+
+        let descriptor_pool = self
+            .context
+            .get_device()
+            .create_descriptor_pool(&pool_info)?;
+
+        Ok(DescriptorPool {
+            device: Shared::clone(self.context.get_device()),
+            descriptor_pool,
+        })
+    }
+}
+
+pub fn write_uniform_buffer(
+    context: &VulkanContext,
+    descriptor_set: vk::DescriptorSet,
+    binding: u32,
+    buffer: &Buffer,
+) {
+    let buffer_infos = [vk::DescriptorBufferInfo::builder()
+        .buffer(buffer.get())
+        .offset(0)
+        .range(vk::WHOLE_SIZE)
+        .build()];
+
+    let descriptor_write = vk::WriteDescriptorSet::builder()
+        .dst_set(descriptor_set)
+        .dst_binding(binding)
+        .dst_array_element(0)
+        .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+        .buffer_info(&buffer_infos)
+        .build();
+
+    context
+        .get_device()
+        .update_descriptor_sets(&[descriptor_write]);
+}
+
+pub fn write_combined_image_sampler(
+    context: &VulkanContext,
+    descriptor_set: vk::DescriptorSet,
+    binding: u32,
+    texture: &Texture,
+) {
+    let descriptor_write = texture.write_descriptor(descriptor_set, binding).build();
+
+    context
+        .get_device()
+        .update_descriptor_sets(&[descriptor_write]);
+}
@@ -1,23 +1,37 @@
+use std::mem;
 use std::os::raw::{c_char, c_void};
-use std::rc::Rc;
 
-use ash::extensions::khr;
-use ash::version::DeviceV1_0;
+use ash::extensions::{khr, nv};
+use ash::version::{DeviceV1_0, InstanceV1_0};
 use ash::vk;
 use ash::vk::PhysicalDeviceDescriptorIndexingFeaturesEXT;
 
+use crate::buffer::IndexType;
 use crate::errors::VulkanError;
+use crate::extended_dynamic_state::{
+    ExtendedDynamicStateFn, PhysicalDeviceExtendedDynamicStateFeatures,
+};
 use crate::extensions::DeviceExtensions;
 use crate::features::Features;
 use crate::instance::VulkanInstance;
 use crate::physical_device::PhysicalDevice;
-
-const FENCE_TIMEOUT: u64 = 100;
+use crate::Shared;
 
 pub struct VulkanDevice {
-    instance: Rc<VulkanInstance>,
+    instance: Shared<VulkanInstance>,
     device: ash::Device,
-    queue: vk::Queue,
+    graphics_queue: vk::Queue,
+    present_queue: vk::Queue,
+    extensions: Vec<DeviceExtensions>,
+    features: Features,
+    max_push_constants_size: u32,
+    calibrated_timestamps_fn: Option<vk::ExtCalibratedTimestampsFn>,
+    extended_dynamic_state_fn: Option<ExtendedDynamicStateFn>,
+    ray_tracing: Option<nv::RayTracing>,
+    #[cfg(feature = "external_memory")]
+    external_memory_fd_fn: Option<vk::KhrExternalMemoryFdFn>,
+    #[cfg(feature = "external_memory")]
+    external_memory_win32_fn: Option<vk::KhrExternalMemoryWin32Fn>,
 }
 
 impl Drop for VulkanDevice {
@@ -33,24 +47,53 @@ impl VulkanDevice {
         &self.device
     }
 
-    pub fn get_queue(&self) -> vk::Queue {
-        self.queue
+    pub fn get_graphics_queue(&self) -> vk::Queue {
+        self.graphics_queue
+    }
+
+    pub fn get_present_queue(&self) -> vk::Queue {
+        self.present_queue
+    }
+
+    pub fn get_max_push_constants_size(&self) -> u32 {
+        self.max_push_constants_size
+    }
+
+    pub fn is_extension_enabled(&self, extension: DeviceExtensions) -> bool {
+        self.extensions.contains(&extension)
+    }
+
+    pub fn enabled_features(&self) -> &Features {
+        &self.features
     }
 
     pub fn queue_wait_idle(&self) -> Result<(), VulkanError> {
-        unsafe { self.device.queue_wait_idle(self.queue) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))?;
+        unsafe { self.device.queue_wait_idle(self.graphics_queue) }
+            .map_err(|err| VulkanError::DeviceError("queue_wait_idle", err))?;
 
         Ok(())
     }
 
+    pub fn present_queue_wait_idle(&self) -> Result<(), VulkanError> {
+        unsafe { self.device.queue_wait_idle(self.present_queue) }
+            .map_err(|err| VulkanError::DeviceError("present_queue_wait_idle", err))
+    }
+
+    pub fn device_wait_idle(&self) -> Result<(), VulkanError> {
+        unsafe { self.device.device_wait_idle() }
+            .map_err(|err| VulkanError::DeviceError("device_wait_idle", err))
+    }
+
     pub fn queue_submit(
         &self,
         submit_info: &[vk::SubmitInfo],
         fence: vk::Fence,
     ) -> Result<(), VulkanError> {
-        unsafe { self.device.queue_submit(self.queue, submit_info, fence) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))?;
+        unsafe {
+            self.device
+                .queue_submit(self.graphics_queue, submit_info, fence)
+        }
+        .map_err(|err| VulkanError::DeviceError("queue_submit", err))?;
 
         Ok(())
     }
@@ -60,7 +103,7 @@ impl VulkanDevice {
         pool_info: &vk::CommandPoolCreateInfo,
     ) -> Result<vk::CommandPool, VulkanError> {
         unsafe { self.device.create_command_pool(pool_info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("create_command_pool", err))
     }
 
     pub fn destroy_command_pool(&self, command_pool: vk::CommandPool) {
@@ -74,7 +117,7 @@ impl VulkanDevice {
         alloc_info: &vk::CommandBufferAllocateInfo,
     ) -> Result<Vec<vk::CommandBuffer>, VulkanError> {
         unsafe { self.device.allocate_command_buffers(&alloc_info) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("allocate_command_buffers", err))
     }
 
     pub fn free_command_buffers(
@@ -90,7 +133,7 @@ impl VulkanDevice {
 
     pub fn create_fence(&self, fence_info: &vk::FenceCreateInfo) -> Result<vk::Fence, VulkanError> {
         unsafe { self.device.create_fence(&fence_info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("create_fence", err))
     }
 
     pub fn destroy_fence(&self, fence: vk::Fence) {
@@ -104,7 +147,7 @@ impl VulkanDevice {
         semaphore_info: &vk::SemaphoreCreateInfo,
     ) -> Result<vk::Semaphore, VulkanError> {
         unsafe { self.device.create_semaphore(&semaphore_info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("create_semaphore", err))
     }
 
     pub fn destroy_semaphore(&self, semaphore: vk::Semaphore) {
@@ -118,7 +161,7 @@ impl VulkanDevice {
         pool_info: &vk::DescriptorPoolCreateInfo,
     ) -> Result<vk::DescriptorPool, VulkanError> {
         unsafe { self.device.create_descriptor_pool(&pool_info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("create_descriptor_pool", err))
     }
 
     pub fn destroy_descriptor_pool(&self, descriptor_pool: vk::DescriptorPool) {
@@ -136,7 +179,7 @@ impl VulkanDevice {
         render_pass_info: &vk::RenderPassCreateInfo,
     ) -> Result<vk::RenderPass, VulkanError> {
         unsafe { self.device.create_render_pass(&render_pass_info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("create_render_pass", err))
     }
 
     pub fn destroy_render_pass(&self, render_pass: vk::RenderPass) {
@@ -150,7 +193,7 @@ impl VulkanDevice {
         view_info: &vk::ImageViewCreateInfo,
     ) -> Result<vk::ImageView, VulkanError> {
         unsafe { self.device.create_image_view(view_info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("create_image_view", err))
     }
 
     pub fn destroy_image_view(&self, image_view: vk::ImageView) {
@@ -161,7 +204,7 @@ impl VulkanDevice {
 
     pub fn create_image(&self, image_info: &vk::ImageCreateInfo) -> Result<vk::Image, VulkanError> {
         unsafe { self.device.create_image(&image_info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("create_image", err))
     }
 
     pub fn destroy_image(&self, image: vk::Image) {
@@ -179,7 +222,7 @@ impl VulkanDevice {
         alloc_info: &vk::MemoryAllocateInfo,
     ) -> Result<vk::DeviceMemory, VulkanError> {
         unsafe { self.device.allocate_memory(&alloc_info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("allocate_memory", err))
     }
 
     pub fn free_memory(&self, memory: vk::DeviceMemory) {
@@ -192,9 +235,10 @@ impl VulkanDevice {
         &self,
         image: vk::Image,
         memory: vk::DeviceMemory,
+        offset: vk::DeviceSize,
     ) -> Result<(), VulkanError> {
-        unsafe { self.device.bind_image_memory(image, memory, 0) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+        unsafe { self.device.bind_image_memory(image, memory, offset) }
+            .map_err(|err| VulkanError::DeviceError("bind_image_memory", err))
     }
 
     pub fn create_frame_buffer(
@@ -202,7 +246,7 @@ impl VulkanDevice {
         info: &vk::FramebufferCreateInfo,
     ) -> Result<vk::Framebuffer, VulkanError> {
         unsafe { self.device.create_framebuffer(info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("create_frame_buffer", err))
     }
 
     pub fn destroy_frame_buffer(&self, frame_buffer: vk::Framebuffer) {
@@ -214,7 +258,7 @@ impl VulkanDevice {
         layout_info: &vk::DescriptorSetLayoutCreateInfo,
     ) -> Result<vk::DescriptorSetLayout, VulkanError> {
         unsafe { self.device.create_descriptor_set_layout(&layout_info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("create_descriptor_set_layout", err))
     }
 
     pub fn destroy_descriptor_set_layout(&self, descriptor_set_layout: vk::DescriptorSetLayout) {
@@ -229,7 +273,7 @@ impl VulkanDevice {
         info: &vk::PipelineLayoutCreateInfo,
     ) -> Result<vk::PipelineLayout, VulkanError> {
         unsafe { self.device.create_pipeline_layout(info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("create_pipeline_layout", err))
     }
 
     pub fn destroy_pipeline_layout(&self, pipeline_layout: vk::PipelineLayout) {
@@ -246,7 +290,18 @@ impl VulkanDevice {
             self.device
                 .create_graphics_pipelines(vk::PipelineCache::null(), infos, None)
         }
-        .map_err(|(_, err)| VulkanError::DeviceError(err.to_string()))
+        .map_err(|(_, err)| VulkanError::DeviceError("create_graphics_pipelines", err))
+    }
+
+    pub fn create_compute_pipelines(
+        &self,
+        infos: &[vk::ComputePipelineCreateInfo],
+    ) -> Result<Vec<vk::Pipeline>, VulkanError> {
+        unsafe {
+            self.device
+                .create_compute_pipelines(vk::PipelineCache::null(), infos, None)
+        }
+        .map_err(|(_, err)| VulkanError::DeviceError("create_compute_pipelines", err))
     }
 
     pub fn destroy_pipeline(&self, pipeline: vk::Pipeline) {
@@ -260,7 +315,7 @@ impl VulkanDevice {
         info: &vk::ShaderModuleCreateInfo,
     ) -> Result<vk::ShaderModule, VulkanError> {
         unsafe { self.device.create_shader_module(info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("create_shader_module", err))
     }
 
     pub fn destroy_shader_module(&self, shader_module: vk::ShaderModule) {
@@ -269,7 +324,7 @@ impl VulkanDevice {
 
     pub fn create_buffer(&self, info: &vk::BufferCreateInfo) -> Result<vk::Buffer, VulkanError> {
         unsafe { self.device.create_buffer(info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("create_buffer", err))
     }
 
     pub fn destroy_buffer(&self, buffer: vk::Buffer) {
@@ -286,9 +341,10 @@ impl VulkanDevice {
         &self,
         buffer: vk::Buffer,
         memory: vk::DeviceMemory,
+        offset: vk::DeviceSize,
     ) -> Result<(), VulkanError> {
-        unsafe { self.device.bind_buffer_memory(buffer, memory, 0) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+        unsafe { self.device.bind_buffer_memory(buffer, memory, offset) }
+            .map_err(|err| VulkanError::DeviceError("bind_buffer_memory", err))
     }
 
     pub fn allocate_descriptor_sets(
@@ -296,16 +352,54 @@ impl VulkanDevice {
         info: &vk::DescriptorSetAllocateInfo,
     ) -> Result<Vec<vk::DescriptorSet>, VulkanError> {
         unsafe { self.device.allocate_descriptor_sets(info) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("allocate_descriptor_sets", err))
     }
 
     pub fn update_descriptor_sets(&self, descriptor_writes: &[vk::WriteDescriptorSet]) {
         unsafe { self.device.update_descriptor_sets(descriptor_writes, &[]) }
     }
 
+    pub fn update_descriptor_image_array(
+        &self,
+        descriptor_set: vk::DescriptorSet,
+        binding: u32,
+        first_array_element: u32,
+        descriptor_type: vk::DescriptorType,
+        image_infos: &[vk::DescriptorImageInfo],
+    ) {
+        let descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(binding)
+            .dst_array_element(first_array_element)
+            .descriptor_type(descriptor_type)
+            .image_info(image_infos)
+            .build();
+
+        self.update_descriptor_sets(&[descriptor_write]);
+    }
+
+    pub fn update_descriptor_buffer_array(
+        &self,
+        descriptor_set: vk::DescriptorSet,
+        binding: u32,
+        first_array_element: u32,
+        descriptor_type: vk::DescriptorType,
+        buffer_infos: &[vk::DescriptorBufferInfo],
+    ) {
+        let descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(binding)
+            .dst_array_element(first_array_element)
+            .descriptor_type(descriptor_type)
+            .buffer_info(buffer_infos)
+            .build();
+
+        self.update_descriptor_sets(&[descriptor_write]);
+    }
+
     pub fn create_sampler(&self, info: &vk::SamplerCreateInfo) -> Result<vk::Sampler, VulkanError> {
         unsafe { self.device.create_sampler(info, None) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("create_sampler", err))
     }
 
     pub fn destroy_sampler(&self, sampler: vk::Sampler) {
@@ -320,12 +414,12 @@ impl VulkanDevice {
         begin_info: &vk::CommandBufferBeginInfo,
     ) -> Result<(), VulkanError> {
         unsafe { self.device.begin_command_buffer(command_buffer, begin_info) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("begin_command_buffer", err))
     }
 
     pub fn end_command_buffer(&self, command_buffer: vk::CommandBuffer) -> Result<(), VulkanError> {
         unsafe { self.device.end_command_buffer(command_buffer) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("end_command_buffer", err))
     }
 
     pub fn cmd_pipeline_barrier(
@@ -351,31 +445,57 @@ impl VulkanDevice {
         }
     }
 
-    pub fn wait_for_fences(&self, fences: &[vk::Fence]) -> Result<(), VulkanError> {
-        unsafe { self.device.wait_for_fences(fences, true, FENCE_TIMEOUT) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+    pub fn get_fence_status(&self, fence: vk::Fence) -> Result<bool, VulkanError> {
+        match unsafe { self.device.get_fence_status(fence) } {
+            Ok(()) => Ok(true),
+            Err(vk::Result::NOT_READY) => Ok(false),
+            Err(err) => Err(VulkanError::DeviceError("get_fence_status", err)),
+        }
+    }
+
+    pub fn wait_for_fences(&self, fences: &[vk::Fence], timeout: u64) -> Result<bool, VulkanError> {
+        match unsafe { self.device.wait_for_fences(fences, true, timeout) } {
+            Ok(()) => Ok(true),
+            Err(vk::Result::TIMEOUT) => Ok(false),
+            Err(err) => Err(VulkanError::DeviceError("wait_for_fences", err)),
+        }
     }
 
     pub fn reset_fences(&self, fences: &[vk::Fence]) -> Result<(), VulkanError> {
         unsafe { self.device.reset_fences(fences) }
-            .map_err(|err| VulkanError::DeviceError(err.to_string()))
+            .map_err(|err| VulkanError::DeviceError("reset_fences", err))
     }
 
     pub fn cmd_begin_render_pass(
         &self,
         command_buffer: vk::CommandBuffer,
         info: &vk::RenderPassBeginInfo,
+        contents: vk::SubpassContents,
     ) {
         unsafe {
             self.device
-                .cmd_begin_render_pass(command_buffer, info, vk::SubpassContents::INLINE);
+                .cmd_begin_render_pass(command_buffer, info, contents);
         }
     }
 
-    pub fn cmd_next_subpass(&self, command_buffer: vk::CommandBuffer) {
+    pub fn cmd_next_subpass(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        contents: vk::SubpassContents,
+    ) {
+        unsafe {
+            self.device.cmd_next_subpass(command_buffer, contents);
+        }
+    }
+
+    pub fn cmd_execute_commands(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        secondary_command_buffers: &[vk::CommandBuffer],
+    ) {
         unsafe {
             self.device
-                .cmd_next_subpass(command_buffer, vk::SubpassContents::INLINE);
+                .cmd_execute_commands(command_buffer, secondary_command_buffers);
         }
     }
 
@@ -416,6 +536,18 @@ impl VulkanDevice {
         }
     }
 
+    pub fn cmd_set_viewport(&self, command_buffer: vk::CommandBuffer, viewports: &[vk::Viewport]) {
+        unsafe {
+            self.device.cmd_set_viewport(command_buffer, 0, viewports);
+        }
+    }
+
+    pub fn cmd_set_scissor(&self, command_buffer: vk::CommandBuffer, scissors: &[vk::Rect2D]) {
+        unsafe {
+            self.device.cmd_set_scissor(command_buffer, 0, scissors);
+        }
+    }
+
     pub fn cmd_bind_vertex_buffers(
         &self,
         command_buffer: vk::CommandBuffer,
@@ -433,17 +565,125 @@ impl VulkanDevice {
         command_buffer: vk::CommandBuffer,
         buffer: vk::Buffer,
         offset: vk::DeviceSize,
+        index_type: IndexType,
     ) {
         unsafe {
             self.device
-                .cmd_bind_index_buffer(command_buffer, buffer, offset, vk::IndexType::UINT32)
+                .cmd_bind_index_buffer(command_buffer, buffer, offset, index_type.into())
         }
     }
 
     pub fn cmd_draw_index(&self, command_buffer: vk::CommandBuffer, index_count: u32) {
+        self.cmd_draw_indexed(command_buffer, index_count, 1, 0, 0, 0);
+    }
+
+    pub fn cmd_push_constants(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        layout: vk::PipelineLayout,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        constants: &[u8],
+    ) -> Result<(), VulkanError> {
+        if offset as usize + constants.len() > self.max_push_constants_size as usize {
+            return Err(VulkanError::PipelineError(format!(
+                "Push constants range {}..{} exceeds maxPushConstantsSize ({})",
+                offset,
+                offset as usize + constants.len(),
+                self.max_push_constants_size
+            )));
+        }
+
+        unsafe {
+            self.device
+                .cmd_push_constants(command_buffer, layout, stage_flags, offset, constants);
+        }
+        Ok(())
+    }
+
+    pub fn cmd_draw(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        vertex_count: u32,
+        instance_count: u32,
+        first_vertex: u32,
+        first_instance: u32,
+    ) {
+        unsafe {
+            self.device.cmd_draw(
+                command_buffer,
+                vertex_count,
+                instance_count,
+                first_vertex,
+                first_instance,
+            );
+        }
+    }
+
+    pub fn cmd_draw_indexed(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        index_count: u32,
+        instance_count: u32,
+        first_index: u32,
+        vertex_offset: i32,
+        first_instance: u32,
+    ) {
+        unsafe {
+            self.device.cmd_draw_indexed(
+                command_buffer,
+                index_count,
+                instance_count,
+                first_index,
+                vertex_offset,
+                first_instance,
+            );
+        }
+    }
+
+    pub fn cmd_dispatch(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        group_count_x: u32,
+        group_count_y: u32,
+        group_count_z: u32,
+    ) {
+        unsafe {
+            self.device
+                .cmd_dispatch(command_buffer, group_count_x, group_count_y, group_count_z);
+        }
+    }
+
+    pub fn cmd_clear_color_image(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        image_layout: vk::ImageLayout,
+        color: &vk::ClearColorValue,
+        ranges: &[vk::ImageSubresourceRange],
+    ) {
         unsafe {
             self.device
-                .cmd_draw_indexed(command_buffer, index_count, 1, 0, 0, 0);
+                .cmd_clear_color_image(command_buffer, image, image_layout, color, ranges);
+        }
+    }
+
+    pub fn cmd_clear_depth_stencil_image(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        image_layout: vk::ImageLayout,
+        depth_stencil: &vk::ClearDepthStencilValue,
+        ranges: &[vk::ImageSubresourceRange],
+    ) {
+        unsafe {
+            self.device.cmd_clear_depth_stencil_image(
+                command_buffer,
+                image,
+                image_layout,
+                depth_stencil,
+                ranges,
+            );
         }
     }
 
@@ -479,16 +719,134 @@ impl VulkanDevice {
         }
     }
 
+    pub fn cmd_copy_image_to_buffer(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        image: vk::Image,
+        image_layout: vk::ImageLayout,
+        buffer: vk::Buffer,
+        regions: &[vk::BufferImageCopy],
+    ) {
+        unsafe {
+            self.device.cmd_copy_image_to_buffer(
+                command_buffer,
+                image,
+                image_layout,
+                buffer,
+                regions,
+            );
+        }
+    }
+
+    pub fn create_query_pool(
+        &self,
+        info: &vk::QueryPoolCreateInfo,
+    ) -> Result<vk::QueryPool, VulkanError> {
+        unsafe { self.device.create_query_pool(info, None) }
+            .map_err(|err| VulkanError::DeviceError("create_query_pool", err))
+    }
+
+    pub fn destroy_query_pool(&self, query_pool: vk::QueryPool) {
+        unsafe {
+            self.device.destroy_query_pool(query_pool, None);
+        }
+    }
+
+    pub fn cmd_reset_query_pool(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        query_pool: vk::QueryPool,
+        first_query: u32,
+        query_count: u32,
+    ) {
+        unsafe {
+            self.device
+                .cmd_reset_query_pool(command_buffer, query_pool, first_query, query_count);
+        }
+    }
+
+    pub fn cmd_begin_query(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        query_pool: vk::QueryPool,
+        query: u32,
+        flags: vk::QueryControlFlags,
+    ) {
+        unsafe {
+            self.device
+                .cmd_begin_query(command_buffer, query_pool, query, flags);
+        }
+    }
+
+    pub fn cmd_end_query(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        query_pool: vk::QueryPool,
+        query: u32,
+    ) {
+        unsafe {
+            self.device.cmd_end_query(command_buffer, query_pool, query);
+        }
+    }
+
+    /// Unlike ash's wrapper, supports queries returning several values: `stride` is the number
+    /// of `u64` written per query.
+    pub fn get_query_pool_results(
+        &self,
+        query_pool: vk::QueryPool,
+        first_query: u32,
+        query_count: u32,
+        stride: usize,
+        data: &mut [u64],
+        flags: vk::QueryResultFlags,
+    ) -> Result<(), VulkanError> {
+        if data.len() < query_count as usize * stride {
+            return Err(VulkanError::QueryPoolError(format!(
+                "{} query results of {} values do not fit in {} values",
+                query_count,
+                stride,
+                data.len()
+            )));
+        }
+
+        let result = unsafe {
+            self.device.fp_v1_0().get_query_pool_results(
+                self.device.handle(),
+                query_pool,
+                first_query,
+                query_count,
+                mem::size_of_val(data),
+                data.as_mut_ptr() as *mut c_void,
+                (stride * mem::size_of::<u64>()) as vk::DeviceSize,
+                flags | vk::QueryResultFlags::TYPE_64,
+            )
+        };
+
+        match result {
+            vk::Result::SUCCESS => Ok(()),
+            err => Err(VulkanError::DeviceError("get_query_pool_results", err)),
+        }
+    }
+
     pub fn map_memory(
         &self,
         memory: vk::DeviceMemory,
         size: vk::DeviceSize,
+    ) -> Result<*mut c_void, VulkanError> {
+        self.map_memory_range(memory, 0, size)
+    }
+
+    pub fn map_memory_range(
+        &self,
+        memory: vk::DeviceMemory,
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
     ) -> Result<*mut c_void, VulkanError> {
         unsafe {
             self.device
-                .map_memory(memory, 0, size, vk::MemoryMapFlags::empty())
+                .map_memory(memory, offset, size, vk::MemoryMapFlags::empty())
         }
-        .map_err(|err| VulkanError::DeviceError(err.to_string()))
+        .map_err(|err| VulkanError::DeviceError("map_memory_range", err))
     }
 
     pub fn unmap_memory(&self, memory: vk::DeviceMemory) {
@@ -496,7 +854,410 @@ impl VulkanDevice {
             self.device.unmap_memory(memory);
         }
     }
-    
+
+    pub fn flush_mapped_memory_ranges(
+        &self,
+        ranges: &[vk::MappedMemoryRange],
+    ) -> Result<(), VulkanError> {
+        unsafe { self.device.flush_mapped_memory_ranges(ranges) }
+            .map_err(|err| VulkanError::DeviceError("flush_mapped_memory_ranges", err))
+    }
+
+    pub fn invalidate_mapped_memory_ranges(
+        &self,
+        ranges: &[vk::MappedMemoryRange],
+    ) -> Result<(), VulkanError> {
+        unsafe { self.device.invalidate_mapped_memory_ranges(ranges) }
+            .map_err(|err| VulkanError::DeviceError("invalidate_mapped_memory_ranges", err))
+    }
+
+    pub fn get_calibrated_timestamps(
+        &self,
+        infos: &[vk::CalibratedTimestampInfoEXT],
+    ) -> Result<(Vec<u64>, u64), VulkanError> {
+        let calibrated_timestamps_fn =
+            self.calibrated_timestamps_fn
+                .as_ref()
+                .ok_or(VulkanError::DeviceError(
+                    "get_calibrated_timestamps",
+                    vk::Result::ERROR_EXTENSION_NOT_PRESENT,
+                ))?;
+
+        let mut timestamps = vec![0; infos.len()];
+        let mut max_deviation = 0;
+        let result = unsafe {
+            calibrated_timestamps_fn.get_calibrated_timestamps_ext(
+                self.device.handle(),
+                infos.len() as u32,
+                infos.as_ptr(),
+                timestamps.as_mut_ptr(),
+                &mut max_deviation,
+            )
+        };
+
+        match result {
+            vk::Result::SUCCESS => Ok((timestamps, max_deviation)),
+            err => Err(VulkanError::DeviceError("get_calibrated_timestamps", err)),
+        }
+    }
+
+    #[cfg(feature = "external_memory")]
+    pub fn get_memory_fd(
+        &self,
+        memory: vk::DeviceMemory,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+    ) -> Result<i32, VulkanError> {
+        let external_memory_fd_fn =
+            self.external_memory_fd_fn
+                .as_ref()
+                .ok_or(VulkanError::DeviceError(
+                    "get_memory_fd",
+                    vk::Result::ERROR_EXTENSION_NOT_PRESENT,
+                ))?;
+
+        let get_fd_info = vk::MemoryGetFdInfoKHR::builder()
+            .memory(memory)
+            .handle_type(handle_type)
+            .build();
+
+        let mut fd = -1;
+        let result = unsafe {
+            external_memory_fd_fn.get_memory_fd_khr(self.device.handle(), &get_fd_info, &mut fd)
+        };
+
+        match result {
+            vk::Result::SUCCESS => Ok(fd),
+            err => Err(VulkanError::DeviceError("get_memory_fd", err)),
+        }
+    }
+
+    #[cfg(feature = "external_memory")]
+    pub fn get_memory_win32_handle(
+        &self,
+        memory: vk::DeviceMemory,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+    ) -> Result<vk::HANDLE, VulkanError> {
+        let external_memory_win32_fn =
+            self.external_memory_win32_fn
+                .as_ref()
+                .ok_or(VulkanError::DeviceError(
+                    "get_memory_win32_handle",
+                    vk::Result::ERROR_EXTENSION_NOT_PRESENT,
+                ))?;
+
+        let get_win32_handle_info = vk::MemoryGetWin32HandleInfoKHR::builder()
+            .memory(memory)
+            .handle_type(handle_type)
+            .build();
+
+        let mut handle = std::ptr::null_mut();
+        let result = unsafe {
+            external_memory_win32_fn.get_memory_win32_handle_khr(
+                self.device.handle(),
+                &get_win32_handle_info,
+                &mut handle,
+            )
+        };
+
+        match result {
+            vk::Result::SUCCESS => Ok(handle),
+            err => Err(VulkanError::DeviceError("get_memory_win32_handle", err)),
+        }
+    }
+
+    fn get_extended_dynamic_state_fn(
+        &self,
+        operation: &'static str,
+    ) -> Result<&ExtendedDynamicStateFn, VulkanError> {
+        self.extended_dynamic_state_fn
+            .as_ref()
+            .ok_or(VulkanError::DeviceError(
+                operation,
+                vk::Result::ERROR_EXTENSION_NOT_PRESENT,
+            ))
+    }
+
+    pub fn cmd_set_cull_mode(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        cull_mode: vk::CullModeFlags,
+    ) -> Result<(), VulkanError> {
+        let extended_dynamic_state_fn = self.get_extended_dynamic_state_fn("cmd_set_cull_mode")?;
+        unsafe { (extended_dynamic_state_fn.cmd_set_cull_mode)(command_buffer, cull_mode) };
+        Ok(())
+    }
+
+    pub fn cmd_set_front_face(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        front_face: vk::FrontFace,
+    ) -> Result<(), VulkanError> {
+        let extended_dynamic_state_fn = self.get_extended_dynamic_state_fn("cmd_set_front_face")?;
+        unsafe { (extended_dynamic_state_fn.cmd_set_front_face)(command_buffer, front_face) };
+        Ok(())
+    }
+
+    pub fn cmd_set_primitive_topology(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        primitive_topology: vk::PrimitiveTopology,
+    ) -> Result<(), VulkanError> {
+        let extended_dynamic_state_fn =
+            self.get_extended_dynamic_state_fn("cmd_set_primitive_topology")?;
+        unsafe {
+            (extended_dynamic_state_fn.cmd_set_primitive_topology)(
+                command_buffer,
+                primitive_topology,
+            )
+        };
+        Ok(())
+    }
+
+    pub fn cmd_set_depth_test_enable(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        enable: bool,
+    ) -> Result<(), VulkanError> {
+        let extended_dynamic_state_fn =
+            self.get_extended_dynamic_state_fn("cmd_set_depth_test_enable")?;
+        unsafe {
+            (extended_dynamic_state_fn.cmd_set_depth_test_enable)(
+                command_buffer,
+                enable as vk::Bool32,
+            )
+        };
+        Ok(())
+    }
+
+    pub fn cmd_set_depth_write_enable(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        enable: bool,
+    ) -> Result<(), VulkanError> {
+        let extended_dynamic_state_fn =
+            self.get_extended_dynamic_state_fn("cmd_set_depth_write_enable")?;
+        unsafe {
+            (extended_dynamic_state_fn.cmd_set_depth_write_enable)(
+                command_buffer,
+                enable as vk::Bool32,
+            )
+        };
+        Ok(())
+    }
+
+    pub fn cmd_set_depth_compare_op(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        compare_op: vk::CompareOp,
+    ) -> Result<(), VulkanError> {
+        let extended_dynamic_state_fn =
+            self.get_extended_dynamic_state_fn("cmd_set_depth_compare_op")?;
+        unsafe { (extended_dynamic_state_fn.cmd_set_depth_compare_op)(command_buffer, compare_op) };
+        Ok(())
+    }
+
+    pub fn cmd_set_depth_bounds_test_enable(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        enable: bool,
+    ) -> Result<(), VulkanError> {
+        let extended_dynamic_state_fn =
+            self.get_extended_dynamic_state_fn("cmd_set_depth_bounds_test_enable")?;
+        unsafe {
+            (extended_dynamic_state_fn.cmd_set_depth_bounds_test_enable)(
+                command_buffer,
+                enable as vk::Bool32,
+            )
+        };
+        Ok(())
+    }
+
+    pub fn cmd_set_stencil_test_enable(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        enable: bool,
+    ) -> Result<(), VulkanError> {
+        let extended_dynamic_state_fn =
+            self.get_extended_dynamic_state_fn("cmd_set_stencil_test_enable")?;
+        unsafe {
+            (extended_dynamic_state_fn.cmd_set_stencil_test_enable)(
+                command_buffer,
+                enable as vk::Bool32,
+            )
+        };
+        Ok(())
+    }
+
+    pub fn cmd_set_stencil_op(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        face_mask: vk::StencilFaceFlags,
+        fail_op: vk::StencilOp,
+        pass_op: vk::StencilOp,
+        depth_fail_op: vk::StencilOp,
+        compare_op: vk::CompareOp,
+    ) -> Result<(), VulkanError> {
+        let extended_dynamic_state_fn = self.get_extended_dynamic_state_fn("cmd_set_stencil_op")?;
+        unsafe {
+            (extended_dynamic_state_fn.cmd_set_stencil_op)(
+                command_buffer,
+                face_mask,
+                fail_op,
+                pass_op,
+                depth_fail_op,
+                compare_op,
+            )
+        };
+        Ok(())
+    }
+
+    fn get_ray_tracing(&self, operation: &'static str) -> Result<&nv::RayTracing, VulkanError> {
+        self.ray_tracing.as_ref().ok_or(VulkanError::DeviceError(
+            operation,
+            vk::Result::ERROR_EXTENSION_NOT_PRESENT,
+        ))
+    }
+
+    pub fn create_acceleration_structure(
+        &self,
+        create_info: &vk::AccelerationStructureCreateInfoNV,
+    ) -> Result<vk::AccelerationStructureNV, VulkanError> {
+        let ray_tracing = self.get_ray_tracing("create_acceleration_structure")?;
+        unsafe { ray_tracing.create_acceleration_structure(create_info, None) }
+            .map_err(|err| VulkanError::DeviceError("create_acceleration_structure", err))
+    }
+
+    pub fn destroy_acceleration_structure(
+        &self,
+        acceleration_structure: vk::AccelerationStructureNV,
+    ) {
+        if let Some(ray_tracing) = &self.ray_tracing {
+            unsafe {
+                ray_tracing.destroy_acceleration_structure(acceleration_structure, None);
+            }
+        }
+    }
+
+    pub fn get_acceleration_structure_memory_requirements(
+        &self,
+        info: &vk::AccelerationStructureMemoryRequirementsInfoNV,
+    ) -> Result<vk::MemoryRequirements, VulkanError> {
+        let ray_tracing = self.get_ray_tracing("get_acceleration_structure_memory_requirements")?;
+        let requirements =
+            unsafe { ray_tracing.get_acceleration_structure_memory_requirements(info) };
+        Ok(requirements.memory_requirements)
+    }
+
+    pub fn bind_acceleration_structure_memory(
+        &self,
+        bind_infos: &[vk::BindAccelerationStructureMemoryInfoNV],
+    ) -> Result<(), VulkanError> {
+        let ray_tracing = self.get_ray_tracing("bind_acceleration_structure_memory")?;
+        unsafe { ray_tracing.bind_acceleration_structure_memory(bind_infos) }
+            .map_err(|err| VulkanError::DeviceError("bind_acceleration_structure_memory", err))
+    }
+
+    pub fn get_acceleration_structure_handle(
+        &self,
+        acceleration_structure: vk::AccelerationStructureNV,
+    ) -> Result<u64, VulkanError> {
+        let ray_tracing = self.get_ray_tracing("get_acceleration_structure_handle")?;
+        unsafe { ray_tracing.get_acceleration_structure_handle(acceleration_structure) }
+            .map_err(|err| VulkanError::DeviceError("get_acceleration_structure_handle", err))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn cmd_build_acceleration_structure(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        info: &vk::AccelerationStructureInfoNV,
+        instance_data: vk::Buffer,
+        update: bool,
+        dst: vk::AccelerationStructureNV,
+        src: vk::AccelerationStructureNV,
+        scratch: vk::Buffer,
+    ) -> Result<(), VulkanError> {
+        let ray_tracing = self.get_ray_tracing("cmd_build_acceleration_structure")?;
+        unsafe {
+            ray_tracing.cmd_build_acceleration_structure(
+                command_buffer,
+                info,
+                instance_data,
+                0,
+                update,
+                dst,
+                src,
+                scratch,
+                0,
+            );
+        }
+        Ok(())
+    }
+
+    pub fn create_ray_tracing_pipelines(
+        &self,
+        infos: &[vk::RayTracingPipelineCreateInfoNV],
+    ) -> Result<Vec<vk::Pipeline>, VulkanError> {
+        let ray_tracing = self.get_ray_tracing("create_ray_tracing_pipelines")?;
+        unsafe { ray_tracing.create_ray_tracing_pipelines(vk::PipelineCache::null(), infos, None) }
+            .map_err(|err| VulkanError::DeviceError("create_ray_tracing_pipelines", err))
+    }
+
+    pub fn get_ray_tracing_shader_group_handles(
+        &self,
+        pipeline: vk::Pipeline,
+        first_group: u32,
+        group_count: u32,
+        data: &mut [u8],
+    ) -> Result<(), VulkanError> {
+        let ray_tracing = self.get_ray_tracing("get_ray_tracing_shader_group_handles")?;
+        unsafe {
+            ray_tracing.get_ray_tracing_shader_group_handles(
+                pipeline,
+                first_group,
+                group_count,
+                data,
+            )
+        }
+        .map_err(|err| VulkanError::DeviceError("get_ray_tracing_shader_group_handles", err))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn cmd_trace_rays(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        shader_binding_table: vk::Buffer,
+        raygen_offset: vk::DeviceSize,
+        miss_offset: vk::DeviceSize,
+        hit_offset: vk::DeviceSize,
+        stride: vk::DeviceSize,
+        width: u32,
+        height: u32,
+        depth: u32,
+    ) -> Result<(), VulkanError> {
+        let ray_tracing = self.get_ray_tracing("cmd_trace_rays")?;
+        unsafe {
+            ray_tracing.cmd_trace_rays(
+                command_buffer,
+                shader_binding_table,
+                raygen_offset,
+                shader_binding_table,
+                miss_offset,
+                stride,
+                shader_binding_table,
+                hit_offset,
+                stride,
+                vk::Buffer::null(),
+                0,
+                0,
+                width,
+                height,
+                depth,
+            );
+        }
+        Ok(())
+    }
+
     pub fn cmd_update_buffer(&self, command_buffer: vk::CommandBuffer, buffer: vk::Buffer, data: &[u8]) {
         unsafe {
             self.device.cmd_update_buffer(command_buffer, buffer, 0, data);
@@ -505,14 +1266,14 @@ impl VulkanDevice {
 }
 
 pub struct VulkanDeviceBuilder<'a> {
-    instance: Rc<VulkanInstance>,
+    instance: Shared<VulkanInstance>,
     physical_device: &'a PhysicalDevice,
     extensions: Vec<DeviceExtensions>,
     features: Features,
 }
 
 impl<'a> VulkanDeviceBuilder<'a> {
-    pub fn new(instance: Rc<VulkanInstance>, physical_device: &'a PhysicalDevice) -> Self {
+    pub fn new(instance: Shared<VulkanInstance>, physical_device: &'a PhysicalDevice) -> Self {
         VulkanDeviceBuilder {
             instance,
             physical_device,
@@ -532,10 +1293,24 @@ impl<'a> VulkanDeviceBuilder<'a> {
     }
 
     pub fn build(self) -> Result<VulkanDevice, VulkanError> {
-        let queue_info = vk::DeviceQueueCreateInfo::builder()
-            .queue_family_index(self.physical_device.get_queue_family())
-            .queue_priorities(&[1.0])
-            .build();
+        let graphics_queue_family = self.physical_device.get_graphics_queue_family();
+        let present_queue_family = self.physical_device.get_present_queue_family();
+
+        let mut queue_families = vec![graphics_queue_family];
+        if present_queue_family != graphics_queue_family {
+            queue_families.push(present_queue_family);
+        }
+
+        let queue_priorities = [1.0];
+        let queue_infos: Vec<vk::DeviceQueueCreateInfo> = queue_families
+            .iter()
+            .map(|queue_family| {
+                vk::DeviceQueueCreateInfo::builder()
+                    .queue_family_index(*queue_family)
+                    .queue_priorities(&queue_priorities)
+                    .build()
+            })
+            .collect();
 
         let extension_names: Vec<*const c_char> = self
             .extensions
@@ -552,25 +1327,137 @@ impl<'a> VulkanDeviceBuilder<'a> {
             .sampler_anisotropy(self.features.sampler_anisotropy)
             .tessellation_shader(self.features.tessellation_shader)
             .fragment_stores_and_atomics(self.features.fragment_stores_and_atomics)
+            .depth_clamp(self.features.depth_clamp)
+            .pipeline_statistics_query(self.features.pipeline_statistics_query)
+            .shader_int64(self.features.shader_int64)
+            .independent_blend(self.features.independent_blend)
+            .wide_lines(self.features.wide_lines)
+            .fill_mode_non_solid(self.features.fill_mode_non_solid)
             .build();
 
+        let extended_dynamic_state_enabled = self
+            .extensions
+            .contains(&DeviceExtensions::ExtExtendedDynamicState);
+        let mut extended_dynamic_state_features =
+            PhysicalDeviceExtendedDynamicStateFeatures::enabled();
+
         let create_info = vk::DeviceCreateInfo::builder()
-            .queue_create_infos(&[queue_info])
+            .queue_create_infos(&queue_infos)
             .enabled_extension_names(&extension_names)
             .enabled_features(&required_features)
-            .push_next(&mut desc_index_features)
+            .push_next(&mut desc_index_features);
+
+        let create_info = if extended_dynamic_state_enabled {
+            create_info.push_next(&mut extended_dynamic_state_features)
+        } else {
+            create_info
+        };
+
+        let mut multiview_features = vk::PhysicalDeviceMultiviewFeatures::builder()
+            .multiview(true)
             .build();
 
+        let create_info = if self.features.multiview {
+            create_info.push_next(&mut multiview_features)
+        } else {
+            create_info
+        };
+
+        let create_info = create_info.build();
+
         let device = self
             .instance
             .create_device(self.physical_device.get(), &create_info)?;
 
-        let queue = unsafe { device.get_device_queue(self.physical_device.get_queue_family(), 0) };
+        let graphics_queue = unsafe { device.get_device_queue(graphics_queue_family, 0) };
+        let present_queue = unsafe { device.get_device_queue(present_queue_family, 0) };
+
+        let calibrated_timestamps_fn = if self
+            .extensions
+            .contains(&DeviceExtensions::ExtCalibratedTimestamps)
+        {
+            Some(vk::ExtCalibratedTimestampsFn::load(|name| unsafe {
+                mem::transmute(
+                    self.instance
+                        .get()
+                        .get_device_proc_addr(device.handle(), name.as_ptr()),
+                )
+            }))
+        } else {
+            None
+        };
+
+        let extended_dynamic_state_fn = if extended_dynamic_state_enabled {
+            Some(
+                ExtendedDynamicStateFn::load(|name| unsafe {
+                    self.instance
+                        .get()
+                        .get_device_proc_addr(device.handle(), name.as_ptr())
+                })
+                .ok_or(VulkanError::DeviceError(
+                    "load_extended_dynamic_state",
+                    vk::Result::ERROR_EXTENSION_NOT_PRESENT,
+                ))?,
+            )
+        } else {
+            None
+        };
+
+        let ray_tracing = if self.extensions.contains(&DeviceExtensions::NvRayTracing) {
+            Some(nv::RayTracing::new(self.instance.get(), &device))
+        } else {
+            None
+        };
+
+        #[cfg(feature = "external_memory")]
+        let external_memory_fd_fn = if self
+            .extensions
+            .contains(&DeviceExtensions::KhrExternalMemoryFd)
+        {
+            Some(vk::KhrExternalMemoryFdFn::load(|name| unsafe {
+                mem::transmute(
+                    self.instance
+                        .get()
+                        .get_device_proc_addr(device.handle(), name.as_ptr()),
+                )
+            }))
+        } else {
+            None
+        };
+
+        #[cfg(feature = "external_memory")]
+        let external_memory_win32_fn = if self
+            .extensions
+            .contains(&DeviceExtensions::KhrExternalMemoryWin32)
+        {
+            Some(vk::KhrExternalMemoryWin32Fn::load(|name| unsafe {
+                mem::transmute(
+                    self.instance
+                        .get()
+                        .get_device_proc_addr(device.handle(), name.as_ptr()),
+                )
+            }))
+        } else {
+            None
+        };
+
+        let max_push_constants_size = self.physical_device.get_limits().max_push_constants_size;
 
         Ok(VulkanDevice {
             instance: self.instance,
             device,
-            queue,
+            graphics_queue,
+            present_queue,
+            extensions: self.extensions,
+            features: self.features,
+            max_push_constants_size,
+            calibrated_timestamps_fn,
+            extended_dynamic_state_fn,
+            ray_tracing,
+            #[cfg(feature = "external_memory")]
+            external_memory_fd_fn,
+            #[cfg(feature = "external_memory")]
+            external_memory_win32_fn,
         })
     }
 }
@@ -1,16 +1,23 @@
+use std::error::Error;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
+use ash::vk;
+
 #[derive(Debug)]
 pub enum VulkanError {
+    AccelerationStructureCreationError(String),
+    BufferError(String),
     DebugCreationError(String),
     DepthResourcesCreationError(String),
-    DeviceError(String),
+    DeviceError(&'static str, vk::Result),
+    FrameBufferCreationError(String),
     ImageCreationError(String),
     InstanceCreationError(String),
     InstanceError(String),
     PipelineError(String),
     PhysicalDeviceCreationError(String),
+    QueryPoolError(String),
     RenderPassCreationError(String),
     ShaderCreationError(String),
     SurfaceError(String),
@@ -18,10 +25,88 @@ pub enum VulkanError {
     SwapchainError(String),
     TextureCreationError(String),
     VertexBufferCreationError(String),
+    VkError(vk::Result),
 }
 
 impl Display for VulkanError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "Vulkan Error: {:?}", self)
+        match self {
+            VulkanError::AccelerationStructureCreationError(msg) => write!(
+                f,
+                "Vulkan Error: acceleration structure creation failed: {}",
+                msg
+            ),
+            VulkanError::BufferError(msg) => write!(f, "Vulkan Error: buffer error: {}", msg),
+            VulkanError::DebugCreationError(msg) => {
+                write!(f, "Vulkan Error: debug messenger creation failed: {}", msg)
+            }
+            VulkanError::DepthResourcesCreationError(msg) => {
+                write!(f, "Vulkan Error: depth resources creation failed: {}", msg)
+            }
+            VulkanError::DeviceError(context, result) => write!(
+                f,
+                "Vulkan Error: {} failed: {} ({:?})",
+                context, result, result
+            ),
+            VulkanError::FrameBufferCreationError(msg) => {
+                write!(f, "Vulkan Error: frame buffer creation failed: {}", msg)
+            }
+            VulkanError::ImageCreationError(msg) => {
+                write!(f, "Vulkan Error: image creation failed: {}", msg)
+            }
+            VulkanError::InstanceCreationError(msg) => {
+                write!(f, "Vulkan Error: instance creation failed: {}", msg)
+            }
+            VulkanError::InstanceError(msg) => write!(f, "Vulkan Error: instance error: {}", msg),
+            VulkanError::PipelineError(msg) => write!(f, "Vulkan Error: pipeline error: {}", msg),
+            VulkanError::PhysicalDeviceCreationError(msg) => {
+                write!(f, "Vulkan Error: physical device selection failed: {}", msg)
+            }
+            VulkanError::QueryPoolError(msg) => {
+                write!(f, "Vulkan Error: query pool error: {}", msg)
+            }
+            VulkanError::RenderPassCreationError(msg) => {
+                write!(f, "Vulkan Error: render pass creation failed: {}", msg)
+            }
+            VulkanError::ShaderCreationError(msg) => {
+                write!(f, "Vulkan Error: shader creation failed: {}", msg)
+            }
+            VulkanError::SurfaceError(msg) => write!(f, "Vulkan Error: surface error: {}", msg),
+            VulkanError::SwapchainCreationError(msg) => {
+                write!(f, "Vulkan Error: swapchain creation failed: {}", msg)
+            }
+            VulkanError::SwapchainError(msg) => write!(f, "Vulkan Error: swapchain error: {}", msg),
+            VulkanError::TextureCreationError(msg) => {
+                write!(f, "Vulkan Error: texture creation failed: {}", msg)
+            }
+            VulkanError::VertexBufferCreationError(msg) => {
+                write!(f, "Vulkan Error: buffer creation failed: {}", msg)
+            }
+            VulkanError::VkError(result) => write!(f, "Vulkan Error: {} ({:?})", result, result),
+        }
+    }
+}
+
+impl Error for VulkanError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            VulkanError::DeviceError(_, result) | VulkanError::VkError(result) => Some(result),
+            _ => None,
+        }
+    }
+}
+
+impl VulkanError {
+    pub fn vk_result(&self) -> Option<vk::Result> {
+        match self {
+            VulkanError::DeviceError(_, result) | VulkanError::VkError(result) => Some(*result),
+            _ => None,
+        }
+    }
+}
+
+impl From<vk::Result> for VulkanError {
+    fn from(result: vk::Result) -> Self {
+        VulkanError::VkError(result)
     }
 }
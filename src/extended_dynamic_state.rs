@@ -0,0 +1,123 @@
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+use ash::vk;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExtendedDynamicState {
+    CullMode,
+    FrontFace,
+    PrimitiveTopology,
+    DepthTestEnable,
+    DepthWriteEnable,
+    DepthCompareOp,
+    DepthBoundsTestEnable,
+    StencilTestEnable,
+    StencilOp,
+}
+
+impl From<ExtendedDynamicState> for vk::DynamicState {
+    fn from(state: ExtendedDynamicState) -> Self {
+        let raw = match state {
+            ExtendedDynamicState::CullMode => 1_000_267_000,
+            ExtendedDynamicState::FrontFace => 1_000_267_001,
+            ExtendedDynamicState::PrimitiveTopology => 1_000_267_002,
+            ExtendedDynamicState::DepthTestEnable => 1_000_267_006,
+            ExtendedDynamicState::DepthWriteEnable => 1_000_267_007,
+            ExtendedDynamicState::DepthCompareOp => 1_000_267_008,
+            ExtendedDynamicState::DepthBoundsTestEnable => 1_000_267_009,
+            ExtendedDynamicState::StencilTestEnable => 1_000_267_010,
+            ExtendedDynamicState::StencilOp => 1_000_267_011,
+        };
+        vk::DynamicState::from_raw(raw)
+    }
+}
+
+#[repr(C)]
+pub(crate) struct PhysicalDeviceExtendedDynamicStateFeatures {
+    s_type: vk::StructureType,
+    p_next: *mut c_void,
+    extended_dynamic_state: vk::Bool32,
+}
+
+unsafe impl vk::ExtendsDeviceCreateInfo for PhysicalDeviceExtendedDynamicStateFeatures {}
+
+impl PhysicalDeviceExtendedDynamicStateFeatures {
+    pub fn enabled() -> Self {
+        PhysicalDeviceExtendedDynamicStateFeatures {
+            s_type: vk::StructureType::from_raw(1_000_267_000),
+            p_next: ptr::null_mut(),
+            extended_dynamic_state: vk::TRUE,
+        }
+    }
+}
+
+type VoidFunction = unsafe extern "system" fn() -> c_void;
+type CmdSetCullMode = unsafe extern "system" fn(vk::CommandBuffer, vk::CullModeFlags);
+type CmdSetFrontFace = unsafe extern "system" fn(vk::CommandBuffer, vk::FrontFace);
+type CmdSetPrimitiveTopology = unsafe extern "system" fn(vk::CommandBuffer, vk::PrimitiveTopology);
+type CmdSetBool = unsafe extern "system" fn(vk::CommandBuffer, vk::Bool32);
+type CmdSetDepthCompareOp = unsafe extern "system" fn(vk::CommandBuffer, vk::CompareOp);
+type CmdSetStencilOp = unsafe extern "system" fn(
+    vk::CommandBuffer,
+    vk::StencilFaceFlags,
+    vk::StencilOp,
+    vk::StencilOp,
+    vk::StencilOp,
+    vk::CompareOp,
+);
+
+pub(crate) struct ExtendedDynamicStateFn {
+    pub cmd_set_cull_mode: CmdSetCullMode,
+    pub cmd_set_front_face: CmdSetFrontFace,
+    pub cmd_set_primitive_topology: CmdSetPrimitiveTopology,
+    pub cmd_set_depth_test_enable: CmdSetBool,
+    pub cmd_set_depth_write_enable: CmdSetBool,
+    pub cmd_set_depth_compare_op: CmdSetDepthCompareOp,
+    pub cmd_set_depth_bounds_test_enable: CmdSetBool,
+    pub cmd_set_stencil_test_enable: CmdSetBool,
+    pub cmd_set_stencil_op: CmdSetStencilOp,
+}
+
+impl ExtendedDynamicStateFn {
+    pub fn load<F>(mut load: F) -> Option<Self>
+    where
+        F: FnMut(&CStr) -> vk::PFN_vkVoidFunction,
+    {
+        let mut load = |name: &[u8]| load(CStr::from_bytes_with_nul(name).unwrap());
+
+        unsafe {
+            Some(ExtendedDynamicStateFn {
+                cmd_set_cull_mode: mem::transmute::<VoidFunction, CmdSetCullMode>(load(
+                    b"vkCmdSetCullModeEXT\0",
+                )?),
+                cmd_set_front_face: mem::transmute::<VoidFunction, CmdSetFrontFace>(load(
+                    b"vkCmdSetFrontFaceEXT\0",
+                )?),
+                cmd_set_primitive_topology: mem::transmute::<VoidFunction, CmdSetPrimitiveTopology>(
+                    load(b"vkCmdSetPrimitiveTopologyEXT\0")?,
+                ),
+                cmd_set_depth_test_enable: mem::transmute::<VoidFunction, CmdSetBool>(load(
+                    b"vkCmdSetDepthTestEnableEXT\0",
+                )?),
+                cmd_set_depth_write_enable: mem::transmute::<VoidFunction, CmdSetBool>(load(
+                    b"vkCmdSetDepthWriteEnableEXT\0",
+                )?),
+                cmd_set_depth_compare_op: mem::transmute::<VoidFunction, CmdSetDepthCompareOp>(
+                    load(b"vkCmdSetDepthCompareOpEXT\0")?,
+                ),
+                cmd_set_depth_bounds_test_enable: mem::transmute::<VoidFunction, CmdSetBool>(load(
+                    b"vkCmdSetDepthBoundsTestEnableEXT\0",
+                )?),
+                cmd_set_stencil_test_enable: mem::transmute::<VoidFunction, CmdSetBool>(load(
+                    b"vkCmdSetStencilTestEnableEXT\0",
+                )?),
+                cmd_set_stencil_op: mem::transmute::<VoidFunction, CmdSetStencilOp>(load(
+                    b"vkCmdSetStencilOpEXT\0",
+                )?),
+            })
+        }
+    }
+}
@@ -2,7 +2,12 @@ use std::ffi::CStr;
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum DeviceExtensions {
+    ExtCalibratedTimestamps,
     ExtDescriptorIndexing,
+    ExtExtendedDynamicState,
+    ExtSamplerFilterMinmax,
+    KhrExternalMemoryFd,
+    KhrExternalMemoryWin32,
     KhrSwapchain,
     NvRayTracing,
     NotImplemented,
@@ -11,7 +16,12 @@ pub enum DeviceExtensions {
 impl From<&str> for DeviceExtensions {
     fn from(name: &str) -> Self {
         match name {
+            "VK_EXT_calibrated_timestamps" => DeviceExtensions::ExtCalibratedTimestamps,
             "VK_EXT_descriptor_indexing" => DeviceExtensions::ExtDescriptorIndexing,
+            "VK_EXT_extended_dynamic_state" => DeviceExtensions::ExtExtendedDynamicState,
+            "VK_EXT_sampler_filter_minmax" => DeviceExtensions::ExtSamplerFilterMinmax,
+            "VK_KHR_external_memory_fd" => DeviceExtensions::KhrExternalMemoryFd,
+            "VK_KHR_external_memory_win32" => DeviceExtensions::KhrExternalMemoryWin32,
             "VK_KHR_swapchain" => DeviceExtensions::KhrSwapchain,
             "VK_NV_ray_tracing" => DeviceExtensions::NvRayTracing,
             _ => DeviceExtensions::NotImplemented,
@@ -22,9 +32,24 @@ impl From<&str> for DeviceExtensions {
 impl DeviceExtensions {
     pub fn name(self) -> &'static CStr {
         match self {
+            DeviceExtensions::ExtCalibratedTimestamps => {
+                CStr::from_bytes_with_nul(b"VK_EXT_calibrated_timestamps\0").unwrap()
+            }
             DeviceExtensions::ExtDescriptorIndexing => {
                 CStr::from_bytes_with_nul(b"VK_EXT_descriptor_indexing\0").unwrap()
             }
+            DeviceExtensions::ExtExtendedDynamicState => {
+                CStr::from_bytes_with_nul(b"VK_EXT_extended_dynamic_state\0").unwrap()
+            }
+            DeviceExtensions::ExtSamplerFilterMinmax => {
+                CStr::from_bytes_with_nul(b"VK_EXT_sampler_filter_minmax\0").unwrap()
+            }
+            DeviceExtensions::KhrExternalMemoryFd => {
+                CStr::from_bytes_with_nul(b"VK_KHR_external_memory_fd\0").unwrap()
+            }
+            DeviceExtensions::KhrExternalMemoryWin32 => {
+                CStr::from_bytes_with_nul(b"VK_KHR_external_memory_win32\0").unwrap()
+            }
             DeviceExtensions::KhrSwapchain => {
                 CStr::from_bytes_with_nul(b"VK_KHR_swapchain\0").unwrap()
             }
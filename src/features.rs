@@ -5,6 +5,13 @@ pub struct Features {
     pub runtime_descriptor_array: bool,
     pub sampler_anisotropy: bool,
     pub fragment_stores_and_atomics: bool,
+    pub depth_clamp: bool,
+    pub pipeline_statistics_query: bool,
+    pub shader_int64: bool,
+    pub independent_blend: bool,
+    pub wide_lines: bool,
+    pub fill_mode_non_solid: bool,
+    pub multiview: bool,
 }
 
 impl Features {
@@ -19,6 +26,13 @@ impl Features {
             runtime_descriptor_array: true,
             sampler_anisotropy: true,
             fragment_stores_and_atomics: true,
+            depth_clamp: true,
+            pipeline_statistics_query: true,
+            shader_int64: true,
+            independent_blend: true,
+            wide_lines: true,
+            fill_mode_non_solid: true,
+            multiview: true,
         }
     }
 }
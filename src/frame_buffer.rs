@@ -1,13 +1,12 @@
-use std::rc::Rc;
-
 use ash::vk;
 
 use crate::device::VulkanDevice;
 use crate::errors::VulkanError;
 use crate::vulkan_context::VulkanContext;
+use crate::Shared;
 
 pub struct FrameBuffers {
-    device: Rc<VulkanDevice>,
+    device: Shared<VulkanDevice>,
     frame_buffers: Vec<vk::Framebuffer>,
 }
 
@@ -30,6 +29,12 @@ pub struct FrameBuffersBuilder<'a> {
     width: u32,
     height: u32,
     frames_count: u32,
+    render_pass: Option<vk::RenderPass>,
+    color_views: Option<Vec<vk::ImageView>>,
+    depth_view: Option<vk::ImageView>,
+    msaa_color_view: Option<vk::ImageView>,
+    view_mask: u32,
+    layers: u32,
 }
 
 impl<'a> FrameBuffersBuilder<'a> {
@@ -39,6 +44,12 @@ impl<'a> FrameBuffersBuilder<'a> {
             width: 0,
             height: 0,
             frames_count: 1,
+            render_pass: None,
+            color_views: None,
+            depth_view: None,
+            msaa_color_view: None,
+            view_mask: 0,
+            layers: 1,
         }
     }
 
@@ -52,22 +63,63 @@ impl<'a> FrameBuffersBuilder<'a> {
         self
     }
 
-    pub fn with_frames_count(mut self, frames_count: u32) -> Self {
-        self.frames_count = frames_count;
+    pub fn with_render_pass(mut self, render_pass: vk::RenderPass) -> Self {
+        self.render_pass = Some(render_pass);
+        self
+    }
+
+    pub fn with_color_views(mut self, color_views: Vec<vk::ImageView>) -> Self {
+        self.frames_count = color_views.len() as u32;
+        self.color_views = Some(color_views);
+        self
+    }
+
+    pub fn with_depth_view(mut self, depth_view: vk::ImageView) -> Self {
+        self.depth_view = Some(depth_view);
+        self
+    }
+
+    pub fn with_msaa_color_view(mut self, msaa_color_view: vk::ImageView) -> Self {
+        self.msaa_color_view = Some(msaa_color_view);
+        self
+    }
+
+    /// View mask of a multiview render pass; every attachment needs a layer for its highest view.
+    pub fn with_view_mask(mut self, view_mask: u32) -> Self {
+        self.view_mask = view_mask;
+        self
+    }
+
+    /// Layer count of the views given with `with_color_views` and `with_depth_view`.
+    pub fn with_layers(mut self, layers: u32) -> Self {
+        self.layers = layers;
         self
     }
 
     pub fn build(self) -> Result<FrameBuffers, VulkanError> {
+        self.check_attachment_layers()?;
+
         let mut frame_buffers = vec![];
 
-        for i in 0..self.frames_count {
-            let image_view = self.context.get_swapchain().get_image_view(i as usize);
+        let render_pass = self
+            .render_pass
+            .unwrap_or_else(|| self.context.get_render_pass().get());
+        let depth_view = self
+            .depth_view
+            .unwrap_or_else(|| self.context.get_depth_resources().get_image_view());
+
+        for i in 0..self.frames_count as usize {
+            let image_view = match &self.color_views {
+                Some(color_views) => color_views[i],
+                None => self.context.get_swapchain().get_image_view(i),
+            };
+            let attachments = match self.msaa_color_view {
+                Some(msaa_color_view) => vec![msaa_color_view, depth_view, image_view],
+                None => vec![image_view, depth_view],
+            };
             let framebuffer_info = vk::FramebufferCreateInfo::builder()
-                .render_pass(self.context.get_render_pass().get())
-                .attachments(&[
-                    image_view,
-                    self.context.get_depth_resources().get_image_view(),
-                ])
+                .render_pass(render_pass)
+                .attachments(&attachments)
                 .width(self.width)
                 .height(self.height)
                 .layers(1)
@@ -81,8 +133,36 @@ impl<'a> FrameBuffersBuilder<'a> {
         }
 
         Ok(FrameBuffers {
-            device: Rc::clone(self.context.get_device()),
+            device: Shared::clone(self.context.get_device()),
             frame_buffers,
         })
     }
+
+    // The multisampled color image always has a single layer.
+    fn check_attachment_layers(&self) -> Result<(), VulkanError> {
+        let required_layers = 32 - self.view_mask.leading_zeros();
+
+        let color_layers = match self.color_views {
+            Some(_) => self.layers,
+            None => self.context.get_image_array_layers(),
+        };
+        let depth_layers = match self.depth_view {
+            Some(_) => self.layers,
+            None => self.context.get_depth_resources().get_layers(),
+        };
+        let msaa_color_layers = match self.msaa_color_view {
+            Some(_) => 1,
+            None => required_layers,
+        };
+
+        let layers = color_layers.min(depth_layers).min(msaa_color_layers);
+        if layers < required_layers {
+            return Err(VulkanError::FrameBufferCreationError(format!(
+                "View mask {:#b} needs {} attachment layers, got {}",
+                self.view_mask, required_layers, layers
+            )));
+        }
+
+        Ok(())
+    }
 }
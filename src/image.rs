@@ -1,6 +1,7 @@
 use ash::vk;
 
 use crate::errors::VulkanError;
+use crate::memory_allocator::{self, Allocation};
 use crate::vulkan_context::VulkanContext;
 
 pub fn create_image(
@@ -11,54 +12,329 @@ pub fn create_image(
     tiling: vk::ImageTiling,
     usage: vk::ImageUsageFlags,
     properties: vk::MemoryPropertyFlags,
-) -> Result<(vk::Image, vk::DeviceMemory), VulkanError> {
-    let image_info = vk::ImageCreateInfo::builder()
-        .image_type(vk::ImageType::TYPE_2D)
-        .extent(
-            vk::Extent3D::builder()
-                .width(width)
-                .height(height)
-                .depth(1)
+) -> Result<(vk::Image, Allocation), VulkanError> {
+    ImageBuilder::new(context)
+        .with_width(width)
+        .with_height(height)
+        .with_format(format)
+        .with_tiling(tiling)
+        .with_usage(usage)
+        .with_properties(properties)
+        .build()
+}
+
+pub struct ImageBuilder<'a> {
+    context: &'a VulkanContext,
+    width: u32,
+    height: u32,
+    format: vk::Format,
+    tiling: vk::ImageTiling,
+    usage: vk::ImageUsageFlags,
+    properties: vk::MemoryPropertyFlags,
+    samples: vk::SampleCountFlags,
+    mip_levels: u32,
+    array_layers: u32,
+    flags: vk::ImageCreateFlags,
+    sharing_mode: vk::SharingMode,
+    queue_family_indices: Vec<u32>,
+}
+
+impl<'a> ImageBuilder<'a> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        ImageBuilder {
+            context,
+            width: 0,
+            height: 0,
+            format: vk::Format::R8G8B8A8_UNORM,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage: vk::ImageUsageFlags::SAMPLED,
+            properties: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            samples: vk::SampleCountFlags::TYPE_1,
+            mip_levels: 1,
+            array_layers: 1,
+            flags: vk::ImageCreateFlags::empty(),
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            queue_family_indices: vec![],
+        }
+    }
+
+    pub fn with_width(mut self, width: u32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_height(mut self, height: u32) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub fn with_format(mut self, format: vk::Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn with_tiling(mut self, tiling: vk::ImageTiling) -> Self {
+        self.tiling = tiling;
+        self
+    }
+
+    pub fn with_usage(mut self, usage: vk::ImageUsageFlags) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn with_properties(mut self, properties: vk::MemoryPropertyFlags) -> Self {
+        self.properties = properties;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn with_mip_levels(mut self, mip_levels: u32) -> Self {
+        self.mip_levels = mip_levels;
+        self
+    }
+
+    pub fn with_array_layers(mut self, array_layers: u32) -> Self {
+        self.array_layers = array_layers;
+        self
+    }
+
+    pub fn with_flags(mut self, flags: vk::ImageCreateFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn with_sharing_mode(
+        mut self,
+        sharing_mode: vk::SharingMode,
+        queue_family_indices: &[u32],
+    ) -> Self {
+        self.sharing_mode = sharing_mode;
+        self.queue_family_indices = queue_family_indices.to_vec();
+        self
+    }
+
+    pub fn build(self) -> Result<(vk::Image, Allocation), VulkanError> {
+        if self.sharing_mode == vk::SharingMode::CONCURRENT && self.queue_family_indices.len() < 2 {
+            return Err(VulkanError::ImageCreationError(String::from(
+                "Concurrent sharing requires at least two queue family indices",
+            )));
+        }
+
+        let image_info = vk::ImageCreateInfo::builder()
+            .flags(self.flags)
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(
+                vk::Extent3D::builder()
+                    .width(self.width)
+                    .height(self.height)
+                    .depth(1)
+                    .build(),
+            )
+            .mip_levels(self.mip_levels)
+            .array_layers(self.array_layers)
+            .format(self.format)
+            .tiling(self.tiling)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(self.usage)
+            .samples(self.samples)
+            .sharing_mode(self.sharing_mode)
+            .queue_family_indices(&self.queue_family_indices)
+            .build();
+
+        let device = self.context.get_device();
+        let image = device.create_image(&image_info)?;
+        let mem_requirements = device.get_image_memory_requirements(image);
+
+        let memory_type_index = self
+            .context
+            .get_instance()
+            .find_memory_type(
+                self.context.get_physical_device().get(),
+                mem_requirements.memory_type_bits,
+                self.properties,
+            )
+            .ok_or_else(|| {
+                VulkanError::ImageCreationError(String::from("Cannot find a memory type"))
+            })?;
+
+        let allocation = memory_allocator::allocate(
+            self.context,
+            mem_requirements,
+            memory_type_index,
+            self.properties,
+        )?;
+
+        device.bind_image_memory(image, allocation.get_memory(), allocation.get_offset())?;
+
+        Ok((image, allocation))
+    }
+}
+
+pub fn is_srgb_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::R8_SRGB
+            | vk::Format::R8G8_SRGB
+            | vk::Format::R8G8B8_SRGB
+            | vk::Format::B8G8R8_SRGB
+            | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::B8G8R8A8_SRGB
+            | vk::Format::A8B8G8R8_SRGB_PACK32
+            | vk::Format::BC1_RGB_SRGB_BLOCK
+            | vk::Format::BC1_RGBA_SRGB_BLOCK
+            | vk::Format::BC2_SRGB_BLOCK
+            | vk::Format::BC3_SRGB_BLOCK
+            | vk::Format::BC7_SRGB_BLOCK
+            | vk::Format::ETC2_R8G8B8_SRGB_BLOCK
+            | vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK
+            | vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK
+            | vk::Format::ASTC_4X4_SRGB_BLOCK
+            | vk::Format::ASTC_5X4_SRGB_BLOCK
+            | vk::Format::ASTC_5X5_SRGB_BLOCK
+            | vk::Format::ASTC_6X5_SRGB_BLOCK
+            | vk::Format::ASTC_6X6_SRGB_BLOCK
+            | vk::Format::ASTC_8X5_SRGB_BLOCK
+            | vk::Format::ASTC_8X6_SRGB_BLOCK
+            | vk::Format::ASTC_8X8_SRGB_BLOCK
+            | vk::Format::ASTC_10X5_SRGB_BLOCK
+            | vk::Format::ASTC_10X6_SRGB_BLOCK
+            | vk::Format::ASTC_10X8_SRGB_BLOCK
+            | vk::Format::ASTC_10X10_SRGB_BLOCK
+            | vk::Format::ASTC_12X10_SRGB_BLOCK
+            | vk::Format::ASTC_12X12_SRGB_BLOCK
+            | vk::Format::PVRTC1_2BPP_SRGB_BLOCK_IMG
+            | vk::Format::PVRTC1_4BPP_SRGB_BLOCK_IMG
+            | vk::Format::PVRTC2_2BPP_SRGB_BLOCK_IMG
+            | vk::Format::PVRTC2_4BPP_SRGB_BLOCK_IMG,
+    )
+}
+
+/// Size in bytes of one texel of the common uncompressed color formats.
+pub fn texel_size(format: vk::Format) -> Option<u32> {
+    match format {
+        vk::Format::R8_UNORM
+        | vk::Format::R8_SNORM
+        | vk::Format::R8_UINT
+        | vk::Format::R8_SINT
+        | vk::Format::R8_SRGB => Some(1),
+        vk::Format::R8G8_UNORM
+        | vk::Format::R8G8_SNORM
+        | vk::Format::R8G8_UINT
+        | vk::Format::R8G8_SINT
+        | vk::Format::R8G8_SRGB
+        | vk::Format::R16_UNORM
+        | vk::Format::R16_SNORM
+        | vk::Format::R16_UINT
+        | vk::Format::R16_SINT
+        | vk::Format::R16_SFLOAT => Some(2),
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SNORM
+        | vk::Format::R8G8B8A8_UINT
+        | vk::Format::R8G8B8A8_SINT
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::A2B10G10R10_UNORM_PACK32
+        | vk::Format::A2R10G10B10_UNORM_PACK32
+        | vk::Format::B10G11R11_UFLOAT_PACK32
+        | vk::Format::R16G16_UNORM
+        | vk::Format::R16G16_SFLOAT
+        | vk::Format::R32_UINT
+        | vk::Format::R32_SINT
+        | vk::Format::R32_SFLOAT => Some(4),
+        vk::Format::R16G16B16A16_UNORM
+        | vk::Format::R16G16B16A16_SFLOAT
+        | vk::Format::R32G32_UINT
+        | vk::Format::R32G32_SINT
+        | vk::Format::R32G32_SFLOAT => Some(8),
+        vk::Format::R32G32B32A32_UINT
+        | vk::Format::R32G32B32A32_SINT
+        | vk::Format::R32G32B32A32_SFLOAT => Some(16),
+        _ => None,
+    }
+}
+
+pub fn create_image_view(
+    context: &VulkanContext,
+    image: vk::Image,
+    format: vk::Format,
+    aspect_flags: vk::ImageAspectFlags,
+) -> Result<vk::ImageView, VulkanError> {
+    let view_info = vk::ImageViewCreateInfo::builder()
+        .image(image)
+        .view_type(vk::ImageViewType::TYPE_2D)
+        .format(format)
+        .subresource_range(
+            vk::ImageSubresourceRange::builder()
+                .aspect_mask(aspect_flags)
+                .base_mip_level(0)
+                .level_count(1)
+                .base_array_layer(0)
+                .layer_count(1)
                 .build(),
         )
-        .mip_levels(1)
-        .array_layers(1)
-        .format(format)
-        .tiling(tiling)
-        .initial_layout(vk::ImageLayout::UNDEFINED)
-        .usage(usage)
-        .samples(vk::SampleCountFlags::TYPE_1)
-        .sharing_mode(vk::SharingMode::EXCLUSIVE)
         .build();
 
-    let image = context.get_device().create_image(&image_info)?;
-    let mem_requirements = context.get_device().get_image_memory_requirements(image);
+    context.get_device().create_image_view(&view_info)
+}
 
-    let memory_type_index = context
-        .get_instance()
-        .find_memory_type(
-            context.get_physical_device().get(),
-            mem_requirements.memory_type_bits,
-            properties,
+pub fn create_image_view_mip_range(
+    context: &VulkanContext,
+    image: vk::Image,
+    format: vk::Format,
+    aspect_flags: vk::ImageAspectFlags,
+    base_mip_level: u32,
+    level_count: u32,
+) -> Result<vk::ImageView, VulkanError> {
+    let view_info = vk::ImageViewCreateInfo::builder()
+        .image(image)
+        .view_type(vk::ImageViewType::TYPE_2D)
+        .format(format)
+        .subresource_range(
+            vk::ImageSubresourceRange::builder()
+                .aspect_mask(aspect_flags)
+                .base_mip_level(base_mip_level)
+                .level_count(level_count)
+                .base_array_layer(0)
+                .layer_count(1)
+                .build(),
         )
-        .ok_or_else(|| {
-            VulkanError::ImageCreationError(String::from("Cannot find a memory type"))
-        })?;
-
-    let alloc_info = vk::MemoryAllocateInfo::builder()
-        .allocation_size(mem_requirements.size)
-        .memory_type_index(memory_type_index)
         .build();
-    let image_memory = context.get_device().allocate_memory(&alloc_info)?;
 
-    context
-        .get_device()
-        .bind_image_memory(image, image_memory)?;
+    context.get_device().create_image_view(&view_info)
+}
 
-    Ok((image, image_memory))
+pub fn create_image_view_array(
+    context: &VulkanContext,
+    image: vk::Image,
+    format: vk::Format,
+    aspect_flags: vk::ImageAspectFlags,
+    layer_count: u32,
+) -> Result<vk::ImageView, VulkanError> {
+    let view_info = vk::ImageViewCreateInfo::builder()
+        .image(image)
+        .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+        .format(format)
+        .subresource_range(
+            vk::ImageSubresourceRange::builder()
+                .aspect_mask(aspect_flags)
+                .base_mip_level(0)
+                .level_count(1)
+                .base_array_layer(0)
+                .layer_count(layer_count)
+                .build(),
+        )
+        .build();
+
+    context.get_device().create_image_view(&view_info)
 }
 
-pub fn create_image_view(
+pub fn create_image_view_cube(
     context: &VulkanContext,
     image: vk::Image,
     format: vk::Format,
@@ -66,7 +342,7 @@ pub fn create_image_view(
 ) -> Result<vk::ImageView, VulkanError> {
     let view_info = vk::ImageViewCreateInfo::builder()
         .image(image)
-        .view_type(vk::ImageViewType::TYPE_2D)
+        .view_type(vk::ImageViewType::CUBE)
         .format(format)
         .subresource_range(
             vk::ImageSubresourceRange::builder()
@@ -74,7 +350,7 @@ pub fn create_image_view(
                 .base_mip_level(0)
                 .level_count(1)
                 .base_array_layer(0)
-                .layer_count(1)
+                .layer_count(6)
                 .build(),
         )
         .build();
@@ -82,6 +358,25 @@ pub fn create_image_view(
     context.get_device().create_image_view(&view_info)
 }
 
+#[derive(Debug, Copy, Clone)]
+pub struct TransitionRange {
+    pub base_mip_level: u32,
+    pub level_count: u32,
+    pub base_array_layer: u32,
+    pub layer_count: u32,
+}
+
+impl Default for TransitionRange {
+    fn default() -> Self {
+        TransitionRange {
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        }
+    }
+}
+
 pub fn transition_image_layout(
     context: &VulkanContext,
     image: vk::Image,
@@ -89,53 +384,142 @@ pub fn transition_image_layout(
     old_layout: vk::ImageLayout,
     new_layout: vk::ImageLayout,
 ) -> Result<(), VulkanError> {
-    let command_buffer = context.begin_single_time_commands()?;
+    transition_image_layout_range(
+        context,
+        image,
+        format,
+        old_layout,
+        new_layout,
+        TransitionRange::default(),
+    )
+}
 
-    let aspect_mask = if new_layout == vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL {
-        if format == vk::Format::D32_SFLOAT_S8_UINT || format == vk::Format::D24_UNORM_S8_UINT {
-            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
-        } else {
-            vk::ImageAspectFlags::DEPTH
+#[derive(Debug, Copy, Clone)]
+pub struct TransitionAccess {
+    pub src_access_mask: vk::AccessFlags,
+    pub dst_access_mask: vk::AccessFlags,
+    pub src_stage: vk::PipelineStageFlags,
+    pub dst_stage: vk::PipelineStageFlags,
+}
+
+impl TransitionAccess {
+    pub fn from_layouts(
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+    ) -> Result<Self, VulkanError> {
+        match (src_access(old_layout), dst_access(new_layout)) {
+            (Some((src_access_mask, src_stage)), Some((dst_access_mask, dst_stage))) => {
+                Ok(TransitionAccess {
+                    src_access_mask,
+                    dst_access_mask,
+                    src_stage,
+                    dst_stage,
+                })
+            }
+            _ => Err(VulkanError::ImageCreationError(format!(
+                "unsupported layout transition from {:?} to {:?}",
+                old_layout, new_layout
+            ))),
         }
-    } else {
-        vk::ImageAspectFlags::COLOR
-    };
-
-    let (src_access_mask, dst_access_mask, src_stage, dst_stage) = if old_layout
-        == vk::ImageLayout::UNDEFINED
-        && (new_layout == vk::ImageLayout::TRANSFER_DST_OPTIMAL
-            || new_layout == vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
-    {
-        (
+    }
+}
+
+fn src_access(layout: vk::ImageLayout) -> Option<(vk::AccessFlags, vk::PipelineStageFlags)> {
+    match layout {
+        vk::ImageLayout::UNDEFINED => Some((
             vk::AccessFlags::empty(),
-            vk::AccessFlags::TRANSFER_WRITE,
             vk::PipelineStageFlags::TOP_OF_PIPE,
+        )),
+        vk::ImageLayout::PREINITIALIZED => {
+            Some((vk::AccessFlags::HOST_WRITE, vk::PipelineStageFlags::HOST))
+        }
+        vk::ImageLayout::PRESENT_SRC_KHR => Some((
+            vk::AccessFlags::empty(),
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+        )),
+        _ => dst_access(layout),
+    }
+}
+
+fn dst_access(layout: vk::ImageLayout) -> Option<(vk::AccessFlags, vk::PipelineStageFlags)> {
+    match layout {
+        vk::ImageLayout::GENERAL => Some((
+            vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+        )),
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL => Some((
+            vk::AccessFlags::TRANSFER_READ,
             vk::PipelineStageFlags::TRANSFER,
-        )
-    } else if old_layout == vk::ImageLayout::TRANSFER_DST_OPTIMAL
-        && new_layout == vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
-    {
-        (
+        )),
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL => Some((
             vk::AccessFlags::TRANSFER_WRITE,
-            vk::AccessFlags::SHADER_READ,
             vk::PipelineStageFlags::TRANSFER,
+        )),
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => Some((
+            vk::AccessFlags::SHADER_READ,
             vk::PipelineStageFlags::FRAGMENT_SHADER,
-        )
-    } else if old_layout == vk::ImageLayout::UNDEFINED
-        && new_layout == vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
-    {
-        (
-            vk::AccessFlags::empty(),
+        )),
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => Some((
+            vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        )),
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL => Some((
             vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
                 | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-            vk::PipelineStageFlags::TOP_OF_PIPE,
-            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-        )
-    } else {
-        return Err(VulkanError::ImageCreationError(String::from(
-            "unsupported layout transition",
-        )));
-    };
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+        )),
+        vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL => Some((
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::FRAGMENT_SHADER,
+        )),
+        vk::ImageLayout::PRESENT_SRC_KHR => Some((
+            vk::AccessFlags::empty(),
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+        )),
+        _ => None,
+    }
+}
+
+fn aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM_S8_UINT
+        | vk::Format::D24_UNORM_S8_UINT
+        | vk::Format::D32_SFLOAT_S8_UINT => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        vk::Format::D16_UNORM | vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D32_SFLOAT => {
+            vk::ImageAspectFlags::DEPTH
+        }
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
+
+pub fn transition_image_layout_range(
+    context: &VulkanContext,
+    image: vk::Image,
+    format: vk::Format,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    range: TransitionRange,
+) -> Result<(), VulkanError> {
+    let access = TransitionAccess::from_layouts(old_layout, new_layout)?;
+
+    transition_image_layout_with_access(
+        context, image, format, old_layout, new_layout, range, access,
+    )
+}
+
+pub fn transition_image_layout_with_access(
+    context: &VulkanContext,
+    image: vk::Image,
+    format: vk::Format,
+    old_layout: vk::ImageLayout,
+    new_layout: vk::ImageLayout,
+    range: TransitionRange,
+    access: TransitionAccess,
+) -> Result<(), VulkanError> {
+    let command_buffer = context.begin_single_time_commands()?;
 
     let barrier = vk::ImageMemoryBarrier::builder()
         .old_layout(old_layout)
@@ -145,21 +529,21 @@ pub fn transition_image_layout(
         .image(image)
         .subresource_range(
             vk::ImageSubresourceRange::builder()
-                .aspect_mask(aspect_mask)
-                .base_mip_level(0)
-                .level_count(1)
-                .base_array_layer(0)
-                .layer_count(1)
+                .aspect_mask(aspect_mask(format))
+                .base_mip_level(range.base_mip_level)
+                .level_count(range.level_count)
+                .base_array_layer(range.base_array_layer)
+                .layer_count(range.layer_count)
                 .build(),
         )
-        .src_access_mask(src_access_mask)
-        .dst_access_mask(dst_access_mask)
+        .src_access_mask(access.src_access_mask)
+        .dst_access_mask(access.dst_access_mask)
         .build();
 
     context.get_device().cmd_pipeline_barrier(
         command_buffer,
-        src_stage,
-        dst_stage,
+        access.src_stage,
+        access.dst_stage,
         vk::DependencyFlags::empty(),
         &[],
         &[],
@@ -168,3 +552,33 @@ pub fn transition_image_layout(
 
     context.end_single_time_commands(command_buffer)
 }
+
+pub fn copy_buffer_to_image(
+    context: &VulkanContext,
+    buffer: vk::Buffer,
+    image: vk::Image,
+    subresource: vk::ImageSubresourceLayers,
+    offset: vk::Offset3D,
+    extent: vk::Extent3D,
+) -> Result<(), VulkanError> {
+    let command_buffer = context.begin_single_time_commands()?;
+
+    let region = vk::BufferImageCopy::builder()
+        .buffer_offset(0)
+        .buffer_row_length(0)
+        .buffer_image_height(0)
+        .image_subresource(subresource)
+        .image_offset(offset)
+        .image_extent(extent)
+        .build();
+
+    context.get_device().cmd_copy_buffer_to_image(
+        command_buffer,
+        buffer,
+        image,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        &[region],
+    );
+
+    context.end_single_time_commands(command_buffer)
+}
@@ -0,0 +1,117 @@
+use ash::vk;
+
+use crate::device::VulkanDevice;
+use crate::Shared;
+
+pub type ImageId = usize;
+
+fn has_write_access(access_mask: vk::AccessFlags) -> bool {
+    access_mask.intersects(
+        vk::AccessFlags::SHADER_WRITE
+            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+            | vk::AccessFlags::TRANSFER_WRITE
+            | vk::AccessFlags::HOST_WRITE
+            | vk::AccessFlags::MEMORY_WRITE,
+    )
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ImageState {
+    pub layout: vk::ImageLayout,
+    pub access_mask: vk::AccessFlags,
+    pub stage: vk::PipelineStageFlags,
+}
+
+impl Default for ImageState {
+    fn default() -> Self {
+        ImageState {
+            layout: vk::ImageLayout::UNDEFINED,
+            access_mask: vk::AccessFlags::empty(),
+            stage: vk::PipelineStageFlags::TOP_OF_PIPE,
+        }
+    }
+}
+
+struct TrackedImage {
+    image: vk::Image,
+    aspect_mask: vk::ImageAspectFlags,
+    state: ImageState,
+}
+
+pub struct ImageStateTracker {
+    device: Shared<VulkanDevice>,
+    images: Vec<TrackedImage>,
+}
+
+impl ImageStateTracker {
+    pub fn new(device: Shared<VulkanDevice>) -> Self {
+        ImageStateTracker {
+            device,
+            images: vec![],
+        }
+    }
+
+    pub fn register(
+        &mut self,
+        image: vk::Image,
+        aspect_mask: vk::ImageAspectFlags,
+        state: ImageState,
+    ) -> ImageId {
+        self.images.push(TrackedImage {
+            image,
+            aspect_mask,
+            state,
+        });
+        self.images.len() - 1
+    }
+
+    pub fn get_state(&self, image_id: ImageId) -> ImageState {
+        self.images[image_id].state
+    }
+
+    pub fn transition(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        image_id: ImageId,
+        new_state: ImageState,
+    ) {
+        let tracked_image = &mut self.images[image_id];
+        // Reads need no barrier between them, but writes must still be made visible when the
+        // layout does not change.
+        if tracked_image.state == new_state && !has_write_access(tracked_image.state.access_mask) {
+            return;
+        }
+
+        let barrier = vk::ImageMemoryBarrier::builder()
+            .old_layout(tracked_image.state.layout)
+            .new_layout(new_state.layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(tracked_image.image)
+            .subresource_range(
+                vk::ImageSubresourceRange::builder()
+                    .aspect_mask(tracked_image.aspect_mask)
+                    .base_mip_level(0)
+                    .level_count(vk::REMAINING_MIP_LEVELS)
+                    .base_array_layer(0)
+                    .layer_count(vk::REMAINING_ARRAY_LAYERS)
+                    .build(),
+            )
+            .src_access_mask(tracked_image.state.access_mask)
+            .dst_access_mask(new_state.access_mask)
+            .build();
+
+        self.device.cmd_pipeline_barrier(
+            command_buffer,
+            tracked_image.state.stage,
+            new_state.stage,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier],
+        );
+
+        tracked_image.state = new_state;
+    }
+}
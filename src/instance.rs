@@ -1,15 +1,22 @@
 use std::ffi::{CStr, CString};
+#[cfg(unix)]
+use std::os::raw::c_ulong;
 use std::os::raw::c_void;
+use std::ptr;
 
 use ash::extensions::{ext, khr};
 use ash::version::{EntryV1_0, InstanceV1_0, InstanceV1_1};
 use ash::vk;
 
-use crate::debug::{DebugOptions, DebugSeverity, DebugType};
+use crate::debug::{self, DebugCallback, DebugOptions, DebugSeverity, DebugType};
 use crate::errors::VulkanError;
 use crate::extensions::DeviceExtensions;
+use crate::windows::PlatformWindow;
 use semver::Version;
 
+// VK_VALIDATION_FEATURE_ENABLE_BEST_PRACTICES_EXT, newer than the headers ash is generated from.
+const VALIDATION_FEATURE_ENABLE_BEST_PRACTICES: i32 = 2;
+
 #[derive(Clone)]
 pub struct ApplicationInfo {
     pub application_name: String,
@@ -34,6 +41,8 @@ pub struct VulkanInstance {
     instance: ash::Instance,
     debug_utils: Option<ash::extensions::ext::DebugUtils>,
     messenger: Option<vk::DebugUtilsMessengerEXT>,
+    // Boxed twice so the messenger's user data keeps pointing at it when the instance moves.
+    _debug_callback: Option<Box<DebugCallback>>,
 }
 
 impl Drop for VulkanInstance {
@@ -52,6 +61,7 @@ impl VulkanInstance {
         &self.instance
     }
 
+    #[cfg(windows)]
     pub fn create_win_32_surface(
         &self,
         hinstance: vk::HINSTANCE,
@@ -67,14 +77,55 @@ impl VulkanInstance {
         let win32_surface_loader = khr::Win32Surface::new(&self.entry, &self.instance);
 
         let surface = unsafe { win32_surface_loader.create_win32_surface(&create_info, None) }
-            .map_err(|err| VulkanError::InstanceError(err.to_string()))?;
+            .map_err(VulkanError::from)?;
+
+        Ok((surface_loader, surface))
+    }
+
+    #[cfg(unix)]
+    pub fn create_xlib_surface(
+        &self,
+        display: *mut c_void,
+        window: c_ulong,
+    ) -> Result<(khr::Surface, vk::SurfaceKHR), VulkanError> {
+        let create_info = vk::XlibSurfaceCreateInfoKHR::builder()
+            .dpy(display as *mut vk::Display)
+            .window(window)
+            .build();
+
+        let surface_loader = khr::Surface::new(&self.entry, &self.instance);
+
+        let xlib_surface_loader = khr::XlibSurface::new(&self.entry, &self.instance);
+
+        let surface = unsafe { xlib_surface_loader.create_xlib_surface(&create_info, None) }
+            .map_err(VulkanError::from)?;
+
+        Ok((surface_loader, surface))
+    }
+
+    #[cfg(unix)]
+    pub fn create_wayland_surface(
+        &self,
+        display: *mut c_void,
+        surface: *mut c_void,
+    ) -> Result<(khr::Surface, vk::SurfaceKHR), VulkanError> {
+        let create_info = vk::WaylandSurfaceCreateInfoKHR::builder()
+            .display(display)
+            .surface(surface)
+            .build();
+
+        let surface_loader = khr::Surface::new(&self.entry, &self.instance);
+
+        let wayland_surface_loader = khr::WaylandSurface::new(&self.entry, &self.instance);
+
+        let surface = unsafe { wayland_surface_loader.create_wayland_surface(&create_info, None) }
+            .map_err(VulkanError::from)?;
 
         Ok((surface_loader, surface))
     }
 
     pub fn enumerate_physical_devices(&self) -> Result<Vec<vk::PhysicalDevice>, VulkanError> {
-        Ok(unsafe { self.instance.enumerate_physical_devices() }
-            .map_err(|err| VulkanError::InstanceError(err.to_string()))?)
+        unsafe { self.instance.enumerate_physical_devices() }.map_err(VulkanError::from)
     }
 
     pub fn get_physical_device_queue_family_properties(
@@ -95,7 +146,7 @@ impl VulkanInstance {
             self.instance
                 .enumerate_device_extension_properties(physical_device)
         }
-        .map_err(|err| VulkanError::InstanceError(err.to_string()))?
+        .map_err(VulkanError::from)?
         .iter()
         .map(|property| {
             let name = unsafe { CStr::from_ptr(property.extension_name.as_ptr()) };
@@ -111,6 +162,23 @@ impl VulkanInstance {
         unsafe { self.instance.get_physical_device_features(device) }
     }
 
+    pub fn get_physical_device_multiview_features(
+        &self,
+        device: vk::PhysicalDevice,
+    ) -> vk::PhysicalDeviceMultiviewFeatures {
+        let mut multiview_features = vk::PhysicalDeviceMultiviewFeatures::default();
+        let mut features = vk::PhysicalDeviceFeatures2 {
+            p_next: &mut multiview_features as *mut _ as *mut c_void,
+            ..Default::default()
+        };
+        unsafe {
+            self.instance
+                .fp_v1_1()
+                .get_physical_device_features2(device, &mut features);
+        }
+        multiview_features
+    }
+
     pub fn get_physical_device_memory_properties(
         &self,
         device: vk::PhysicalDevice,
@@ -118,6 +186,13 @@ impl VulkanInstance {
         unsafe { self.instance.get_physical_device_memory_properties(device) }
     }
 
+    pub fn get_physical_device_properties(
+        &self,
+        device: vk::PhysicalDevice,
+    ) -> vk::PhysicalDeviceProperties {
+        unsafe { self.instance.get_physical_device_properties(device) }
+    }
+
     pub fn get_physical_device_properties2(
         &self,
         device: vk::PhysicalDevice,
@@ -149,7 +224,7 @@ impl VulkanInstance {
             self.instance
                 .create_device(physical_device, create_info, None)
         }
-        .map_err(|err| VulkanError::InstanceError(err.to_string()))
+        .map_err(VulkanError::from)
     }
 
     pub fn find_memory_type(
@@ -166,6 +241,7 @@ impl VulkanInstance {
         memory_properties
             .memory_types
             .iter()
+            .take(memory_properties.memory_type_count as usize)
             .enumerate()
             .find_map(|(index, memory_type)| {
                 if type_filter & (1 << index as u32) != 0
@@ -182,28 +258,18 @@ impl VulkanInstance {
         severity: vk::DebugUtilsMessageSeverityFlagsEXT,
         ty: vk::DebugUtilsMessageTypeFlagsEXT,
         callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-        _: *mut c_void,
+        user_data: *mut c_void,
     ) -> u32 {
         let message = CStr::from_ptr((*callback_data).p_message);
 
-        let message = if ty.contains(vk::DebugUtilsMessageTypeFlagsEXT::GENERAL) {
-            format!("General Layer: {:?}", message)
-        } else if ty.contains(vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION) {
-            format!("Validation layer: {:?}", message)
-        } else {
-            format!("Performance Layer: {:?}", message)
-        };
-
-        if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE) {
-            log::trace!("{}", message);
-        } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
-            log::info!("{}", message);
-        } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
-            log::warn!("{}", message);
-        } else if severity.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
-            log::error!("{}", message);
+        if !user_data.is_null() {
+            let callback = &*(user_data as *const DebugCallback);
+            callback(severity.into(), ty.into(), &message.to_string_lossy());
+            return vk::FALSE;
         }
 
+        debug::log_message(severity.into(), ty.into(), &message.to_string_lossy());
+
         vk::FALSE
     }
 }
@@ -211,6 +277,8 @@ impl VulkanInstance {
 pub struct VulkanInstanceBuilder<'a> {
     debug_options: DebugOptions,
     application_info: Option<&'a ApplicationInfo>,
+    window: Option<PlatformWindow>,
+    debug_callback: Option<DebugCallback>,
 }
 
 impl<'a> VulkanInstanceBuilder<'a> {
@@ -218,9 +286,17 @@ impl<'a> VulkanInstanceBuilder<'a> {
         VulkanInstanceBuilder {
             debug_options: DebugOptions::default(),
             application_info: None,
+            window: None,
+            debug_callback: None,
         }
     }
 
+    /// Replaces the default `log` output of the debug messenger.
+    pub fn with_debug_callback(mut self, debug_callback: DebugCallback) -> Self {
+        self.debug_callback = Some(debug_callback);
+        self
+    }
+
     pub fn with_debug_options(mut self, debug_options: DebugOptions) -> Self {
         self.debug_options = debug_options;
         self
@@ -231,6 +307,11 @@ impl<'a> VulkanInstanceBuilder<'a> {
         self
     }
 
+    pub fn with_window(mut self, window: PlatformWindow) -> Self {
+        self.window = Some(window);
+        self
+    }
+
     pub fn build(self) -> Result<VulkanInstance, VulkanError> {
         let application_info = self.application_info.unwrap();
 
@@ -260,10 +341,10 @@ impl<'a> VulkanInstanceBuilder<'a> {
             .build();
 
         let mut layers = vec![];
-        let mut extensions = vec![
-            khr::Surface::name().as_ptr(),
-            khr::Win32Surface::name().as_ptr(),
-        ];
+        let mut extensions = vec![khr::Surface::name().as_ptr()];
+        if let Some(window) = &self.window {
+            extensions.push(window.surface_extension_name().as_ptr());
+        }
 
         let debug_enabled = self.debug_options.debug_type != DebugType::none()
             && self.debug_options.debug_severity != DebugSeverity::none();
@@ -274,22 +355,53 @@ impl<'a> VulkanInstanceBuilder<'a> {
             extensions.push(ext::DebugUtils::name().as_ptr())
         }
 
-        let create_info = vk::InstanceCreateInfo::builder()
+        let mut enabled_validation_features = vec![];
+        if debug_enabled && self.debug_options.gpu_assisted {
+            enabled_validation_features.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED);
+            enabled_validation_features
+                .push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT);
+        }
+        if debug_enabled && self.debug_options.best_practices {
+            enabled_validation_features.push(vk::ValidationFeatureEnableEXT::from_raw(
+                VALIDATION_FEATURE_ENABLE_BEST_PRACTICES,
+            ));
+        }
+        if !enabled_validation_features.is_empty() {
+            extensions.push(vk::ExtValidationFeaturesFn::name().as_ptr());
+        }
+
+        let mut validation_features = vk::ValidationFeaturesEXT::builder()
+            .enabled_validation_features(&enabled_validation_features)
+            .build();
+
+        let mut create_info = vk::InstanceCreateInfo::builder()
             .application_info(&application_info)
             .enabled_layer_names(layers.as_slice())
-            .enabled_extension_names(extensions.as_slice())
-            .build();
+            .enabled_extension_names(extensions.as_slice());
+
+        if !enabled_validation_features.is_empty() {
+            create_info = create_info.push_next(&mut validation_features);
+        }
+
+        let create_info = create_info.build();
 
         let entry =
             ash::Entry::new().map_err(|err| VulkanError::InstanceCreationError(err.to_string()))?;
         let instance = unsafe { entry.create_instance(&create_info, None) }
             .map_err(|err| VulkanError::InstanceCreationError(err.to_string()))?;
 
+        let debug_callback = self.debug_callback.map(Box::new);
+        let user_data = match &debug_callback {
+            Some(callback) => &**callback as *const DebugCallback as *mut c_void,
+            None => ptr::null_mut(),
+        };
+
         let (debug_utils, messenger) = if debug_enabled {
             let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
                 .message_severity(self.debug_options.debug_severity.into())
                 .message_type(self.debug_options.debug_type.into())
                 .pfn_user_callback(Some(VulkanInstance::vulkan_debug_callback))
+                .user_data(user_data)
                 .build();
 
             let debug_utils = Some(ext::DebugUtils::new(&entry, &instance));
@@ -300,7 +412,7 @@ impl<'a> VulkanInstanceBuilder<'a> {
                         .unwrap()
                         .create_debug_utils_messenger(&debug_info, None)
                 }
-                .map_err(|err| VulkanError::DebugCreationError(err.to_string()))?,
+                .map_err(VulkanError::from)?,
             );
             (debug_utils, messenger)
         } else {
@@ -312,6 +424,7 @@ impl<'a> VulkanInstanceBuilder<'a> {
             instance,
             debug_utils,
             messenger,
+            _debug_callback: debug_callback,
         })
     }
 }
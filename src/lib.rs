@@ -1,22 +1,58 @@
+pub use physical_device::{DriverInfo, PhysicalDeviceInfo, PhysicalDeviceScorer};
 pub use semver::Version;
 
+/// Shares the instance and device between wrappers. With the `multithread` feature this is an
+/// `Arc` and the sampler cache and memory allocator sit behind a `Mutex`: `VulkanDevice` is
+/// `Send + Sync`, and buffers, textures and the `VulkanContext` itself are `Send`. The context is
+/// not `Sync`, as its command pool and queue are used without locking.
+#[cfg(not(feature = "multithread"))]
+pub use std::rc::Rc as Shared;
+#[cfg(feature = "multithread")]
+pub use std::sync::Arc as Shared;
+
+pub mod acceleration_structure;
 pub mod buffer;
+pub mod color_resources;
 pub mod debug;
+pub mod depth_resources;
+pub mod descriptor;
 pub mod device;
 pub mod errors;
+pub mod extended_dynamic_state;
 pub mod extensions;
 pub mod features;
 pub mod image;
+pub mod image_state;
+pub mod memory_allocator;
+pub mod mesh;
+pub mod pipeline;
+pub mod query_pool;
+pub mod render_pass;
+pub mod sampler;
+pub mod secondary_command_buffers;
 pub mod shader_module;
+pub mod swapchain;
 pub mod texture;
+pub mod uniform_descriptor;
 pub mod vulkan_context;
 pub mod windows;
 
 mod command_buffers;
-mod depth_resources;
 mod frame_buffer;
 mod instance;
+mod lock;
 mod physical_device;
-mod render_pass;
 mod surface;
-mod swapchain;
+
+#[cfg(feature = "multithread")]
+#[allow(dead_code)]
+fn assert_thread_safety() {
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+
+    assert_send::<device::VulkanDevice>();
+    assert_sync::<device::VulkanDevice>();
+    assert_send::<buffer::Buffer>();
+    assert_send::<texture::Texture>();
+    assert_send::<vulkan_context::VulkanContext>();
+}
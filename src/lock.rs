@@ -0,0 +1,36 @@
+#[cfg(not(feature = "multithread"))]
+use std::cell::{RefCell, RefMut};
+#[cfg(feature = "multithread")]
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Interior mutability for state reachable through `Shared`: a `RefCell` by default, a `Mutex`
+/// with the `multithread` feature.
+#[cfg(not(feature = "multithread"))]
+pub(crate) struct Lock<T>(RefCell<T>);
+
+#[cfg(not(feature = "multithread"))]
+impl<T> Lock<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Lock(RefCell::new(value))
+    }
+
+    pub(crate) fn lock(&self) -> RefMut<'_, T> {
+        self.0.borrow_mut()
+    }
+}
+
+#[cfg(feature = "multithread")]
+pub(crate) struct Lock<T>(Mutex<T>);
+
+#[cfg(feature = "multithread")]
+impl<T> Lock<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Lock(Mutex::new(value))
+    }
+
+    // The guarded state is only updated once a Vulkan call succeeded, so it stays consistent
+    // even if a holder panicked.
+    pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,261 @@
+use std::mem;
+
+use ash::vk;
+
+use crate::device::VulkanDevice;
+use crate::errors::VulkanError;
+use crate::lock::Lock;
+use crate::vulkan_context::VulkanContext;
+use crate::Shared;
+
+pub const DEFAULT_BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+
+pub struct Allocation {
+    device: Shared<VulkanDevice>,
+    allocator: Option<Shared<MemoryAllocator>>,
+    memory: vk::DeviceMemory,
+    offset: vk::DeviceSize,
+    size: vk::DeviceSize,
+}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        if self.memory == vk::DeviceMemory::null() {
+            return;
+        }
+
+        match &self.allocator {
+            Some(allocator) => allocator.free(self.memory, self.offset, self.size),
+            None => self.device.free_memory(self.memory),
+        }
+    }
+}
+
+impl Allocation {
+    pub(crate) fn dedicated(
+        device: &Shared<VulkanDevice>,
+        memory: vk::DeviceMemory,
+        size: vk::DeviceSize,
+    ) -> Self {
+        Allocation {
+            device: Shared::clone(device),
+            allocator: None,
+            memory,
+            offset: 0,
+            size,
+        }
+    }
+
+    pub fn get_memory(&self) -> vk::DeviceMemory {
+        self.memory
+    }
+
+    pub fn get_offset(&self) -> vk::DeviceSize {
+        self.offset
+    }
+
+    pub fn get_size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    pub fn is_suballocated(&self) -> bool {
+        self.allocator.is_some()
+    }
+
+    /// Gives up a dedicated allocation so that dropping this no longer frees it.
+    pub(crate) fn release(&mut self) -> vk::DeviceMemory {
+        debug_assert!(!self.is_suballocated());
+        mem::replace(&mut self.memory, vk::DeviceMemory::null())
+    }
+}
+
+struct MemoryBlock {
+    memory: vk::DeviceMemory,
+    memory_type_index: u32,
+    // Free ranges as (offset, size), sorted by offset and never adjacent.
+    free_ranges: Vec<(vk::DeviceSize, vk::DeviceSize)>,
+}
+
+impl MemoryBlock {
+    fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Option<vk::DeviceSize> {
+        let (index, offset) = self.free_ranges.iter().enumerate().find_map(
+            |(index, (range_offset, range_size))| {
+                let offset = align_up(*range_offset, alignment);
+                if offset + size <= range_offset + range_size {
+                    Some((index, offset))
+                } else {
+                    None
+                }
+            },
+        )?;
+
+        let (range_offset, range_size) = self.free_ranges.remove(index);
+        let range_end = range_offset + range_size;
+        if offset + size < range_end {
+            self.free_ranges
+                .insert(index, (offset + size, range_end - offset - size));
+        }
+        if range_offset < offset {
+            self.free_ranges
+                .insert(index, (range_offset, offset - range_offset));
+        }
+
+        Some(offset)
+    }
+
+    fn free(&mut self, offset: vk::DeviceSize, size: vk::DeviceSize) {
+        let index = self
+            .free_ranges
+            .iter()
+            .position(|(range_offset, _)| *range_offset > offset)
+            .unwrap_or(self.free_ranges.len());
+        self.free_ranges.insert(index, (offset, size));
+
+        if index + 1 < self.free_ranges.len() {
+            let (next_offset, next_size) = self.free_ranges[index + 1];
+            if offset + size == next_offset {
+                self.free_ranges[index].1 += next_size;
+                self.free_ranges.remove(index + 1);
+            }
+        }
+        if index > 0 {
+            let (previous_offset, previous_size) = self.free_ranges[index - 1];
+            if previous_offset + previous_size == offset {
+                self.free_ranges[index - 1].1 += self.free_ranges[index].1;
+                self.free_ranges.remove(index);
+            }
+        }
+    }
+}
+
+/// Hands out sub-regions of a few large `vk::DeviceMemory` blocks per memory type, so scenes
+/// with many resources stay under `maxMemoryAllocationCount`.
+pub struct MemoryAllocator {
+    device: Shared<VulkanDevice>,
+    block_size: vk::DeviceSize,
+    granularity: vk::DeviceSize,
+    blocks: Lock<Vec<MemoryBlock>>,
+}
+
+impl Drop for MemoryAllocator {
+    fn drop(&mut self) {
+        for block in self.blocks.lock().iter() {
+            self.device.free_memory(block.memory);
+        }
+    }
+}
+
+impl MemoryAllocator {
+    pub(crate) fn new(
+        device: Shared<VulkanDevice>,
+        block_size: vk::DeviceSize,
+        buffer_image_granularity: vk::DeviceSize,
+    ) -> Self {
+        MemoryAllocator {
+            device,
+            block_size,
+            granularity: buffer_image_granularity.max(1),
+            blocks: Lock::new(vec![]),
+        }
+    }
+
+    pub fn get_block_count(&self) -> usize {
+        self.blocks.lock().len()
+    }
+
+    pub fn allocate(
+        self: &Shared<Self>,
+        requirements: vk::MemoryRequirements,
+        memory_type_index: u32,
+    ) -> Result<Allocation, VulkanError> {
+        // Aligning everything to the buffer/image granularity lets linear and optimal
+        // resources share a block without aliasing.
+        let alignment = requirements.alignment.max(self.granularity);
+        let size = align_up(requirements.size, self.granularity);
+
+        let mut blocks = self.blocks.lock();
+        let found = blocks
+            .iter_mut()
+            .filter(|block| block.memory_type_index == memory_type_index)
+            .find_map(|block| {
+                block
+                    .allocate(size, alignment)
+                    .map(|offset| (block.memory, offset))
+            });
+
+        let (memory, offset) = match found {
+            Some(found) => found,
+            None => {
+                let block_size = self.block_size.max(size);
+                let alloc_info = vk::MemoryAllocateInfo::builder()
+                    .allocation_size(block_size)
+                    .memory_type_index(memory_type_index)
+                    .build();
+                let memory = self.device.allocate_memory(&alloc_info)?;
+
+                let mut block = MemoryBlock {
+                    memory,
+                    memory_type_index,
+                    free_ranges: vec![(0, block_size)],
+                };
+                let offset = block.allocate(size, alignment).unwrap_or(0);
+                blocks.push(block);
+                (memory, offset)
+            }
+        };
+
+        Ok(Allocation {
+            device: Shared::clone(&self.device),
+            allocator: Some(Shared::clone(self)),
+            memory,
+            offset,
+            size,
+        })
+    }
+
+    fn free(&self, memory: vk::DeviceMemory, offset: vk::DeviceSize, size: vk::DeviceSize) {
+        if let Some(block) = self
+            .blocks
+            .lock()
+            .iter_mut()
+            .find(|block| block.memory == memory)
+        {
+            block.free(offset, size);
+        }
+    }
+}
+
+/// Suballocates from the context's allocator when it has one, except for host visible memory
+/// which stays dedicated so that it can be mapped independently.
+pub(crate) fn allocate(
+    context: &VulkanContext,
+    requirements: vk::MemoryRequirements,
+    memory_type_index: u32,
+    properties: vk::MemoryPropertyFlags,
+) -> Result<Allocation, VulkanError> {
+    match context.get_memory_allocator() {
+        Some(allocator) if !properties.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) => {
+            allocator.allocate(requirements, memory_type_index)
+        }
+        _ => {
+            let alloc_info = vk::MemoryAllocateInfo::builder()
+                .allocation_size(requirements.size)
+                .memory_type_index(memory_type_index)
+                .build();
+            let memory = context.get_device().allocate_memory(&alloc_info)?;
+            Ok(Allocation::dedicated(
+                context.get_device(),
+                memory,
+                requirements.size,
+            ))
+        }
+    }
+}
+
+fn align_up(value: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+    value.div_ceil(alignment) * alignment
+}
@@ -0,0 +1,115 @@
+use std::mem;
+use std::os::raw::c_void;
+
+use ash::vk;
+
+use crate::buffer::{Buffer, BufferBuilder, BufferType, IndexType};
+use crate::device::VulkanDevice;
+use crate::errors::VulkanError;
+use crate::vulkan_context::VulkanContext;
+use crate::Shared;
+
+pub struct Mesh {
+    device: Shared<VulkanDevice>,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    index_count: u32,
+}
+
+impl Mesh {
+    pub fn get_vertex_buffer(&self) -> &Buffer {
+        &self.vertex_buffer
+    }
+
+    pub fn get_index_buffer(&self) -> &Buffer {
+        &self.index_buffer
+    }
+
+    pub fn get_index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    pub fn bind_and_draw(&self, command_buffer: vk::CommandBuffer) {
+        self.device
+            .cmd_bind_vertex_buffers(command_buffer, &[self.vertex_buffer.get()], &[0]);
+        self.device.cmd_bind_index_buffer(
+            command_buffer,
+            self.index_buffer.get(),
+            0,
+            IndexType::U32,
+        );
+        self.device.cmd_draw_index(command_buffer, self.index_count);
+    }
+}
+
+pub struct MeshBuilder<'a, V: Copy> {
+    context: &'a VulkanContext,
+    vertices: &'a [V],
+    indices: &'a [u32],
+}
+
+impl<'a, V: Copy> MeshBuilder<'a, V> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        MeshBuilder {
+            context,
+            vertices: &[],
+            indices: &[],
+        }
+    }
+
+    pub fn with_vertices(mut self, vertices: &'a [V]) -> Self {
+        self.vertices = vertices;
+        self
+    }
+
+    pub fn with_indices(mut self, indices: &'a [u32]) -> Self {
+        self.indices = indices;
+        self
+    }
+
+    pub fn build(self) -> Result<Mesh, VulkanError> {
+        if self.vertices.is_empty() || self.indices.is_empty() {
+            return Err(VulkanError::VertexBufferCreationError(String::from(
+                "A mesh needs at least one vertex and one index",
+            )));
+        }
+
+        let vertex_buffer = self.upload(BufferType::VertexOnly, self.vertices)?;
+        let index_buffer = self.upload(BufferType::IndexOnly, self.indices)?;
+
+        Ok(Mesh {
+            device: Shared::clone(self.context.get_device()),
+            vertex_buffer,
+            index_buffer,
+            index_count: self.indices.len() as u32,
+        })
+    }
+
+    fn upload<T: Copy>(&self, ty: BufferType, data: &[T]) -> Result<Buffer, VulkanError> {
+        let buffer_size = mem::size_of_val(data) as vk::DeviceSize;
+
+        let staging_buffer = BufferBuilder::new(self.context)
+            .with_type(BufferType::Staging)
+            .with_size(buffer_size)
+            .build()?;
+
+        staging_buffer.copy_data(data.as_ptr() as *const c_void)?;
+
+        let buffer = BufferBuilder::new(self.context)
+            .with_type(ty)
+            .with_size(buffer_size)
+            .build()?;
+
+        let command_buffer = self.context.begin_single_time_commands()?;
+        let copy_regions = [vk::BufferCopy::builder().size(buffer_size).build()];
+        self.context.get_device().cmd_copy_buffer(
+            command_buffer,
+            staging_buffer.get(),
+            buffer.get(),
+            &copy_regions,
+        );
+        self.context.end_single_time_commands(command_buffer)?;
+
+        Ok(buffer)
+    }
+}
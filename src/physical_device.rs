@@ -1,3 +1,4 @@
+use ash::extensions::nv::RayTracing;
 use ash::vk;
 
 use crate::errors::VulkanError;
@@ -5,12 +6,113 @@ use crate::extensions::DeviceExtensions;
 use crate::features::Features;
 use crate::instance::VulkanInstance;
 use crate::surface::Surface;
-use std::rc::Rc;
+use crate::Shared;
+use semver::Version;
+use std::ffi::CStr;
+
+const VENDOR_ID_AMD: u32 = 0x1002;
+const VENDOR_ID_IMGTEC: u32 = 0x1010;
+const VENDOR_ID_NVIDIA: u32 = 0x10DE;
+const VENDOR_ID_ARM: u32 = 0x13B5;
+const VENDOR_ID_QUALCOMM: u32 = 0x5143;
+const VENDOR_ID_INTEL: u32 = 0x8086;
+
+#[derive(Debug, Clone)]
+pub struct DriverInfo {
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub driver_version: Version,
+    pub vendor_name: &'static str,
+}
+
+impl DriverInfo {
+    fn new(properties: &vk::PhysicalDeviceProperties) -> Self {
+        DriverInfo {
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            driver_version: Self::decode_driver_version(
+                properties.vendor_id,
+                properties.driver_version,
+            ),
+            vendor_name: Self::vendor_name(properties.vendor_id),
+        }
+    }
+
+    fn vendor_name(vendor_id: u32) -> &'static str {
+        match vendor_id {
+            VENDOR_ID_AMD => "AMD",
+            VENDOR_ID_IMGTEC => "ImgTec",
+            VENDOR_ID_NVIDIA => "NVIDIA",
+            VENDOR_ID_ARM => "ARM",
+            VENDOR_ID_QUALCOMM => "Qualcomm",
+            VENDOR_ID_INTEL => "Intel",
+            _ => "Unknown",
+        }
+    }
+
+    fn decode_driver_version(vendor_id: u32, version: u32) -> Version {
+        match vendor_id {
+            VENDOR_ID_NVIDIA => Version::new(
+                u64::from((version >> 22) & 0x3ff),
+                u64::from((version >> 14) & 0xff),
+                u64::from((version >> 6) & 0xff),
+            ),
+            VENDOR_ID_INTEL if cfg!(windows) => {
+                Version::new(u64::from(version >> 14), u64::from(version & 0x3fff), 0)
+            }
+            _ => Version::new(
+                u64::from(ash::vk_version_major!(version)),
+                u64::from(ash::vk_version_minor!(version)),
+                u64::from(ash::vk_version_patch!(version)),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PhysicalDeviceInfo {
+    pub name: String,
+    pub device_type: vk::PhysicalDeviceType,
+    pub driver_info: DriverInfo,
+    pub properties: vk::PhysicalDeviceProperties,
+    pub memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pub features: vk::PhysicalDeviceFeatures,
+    pub is_suitable: bool,
+}
+
+impl PhysicalDeviceInfo {
+    fn new(
+        instance: &VulkanInstance,
+        physical_device: vk::PhysicalDevice,
+        is_suitable: bool,
+    ) -> Self {
+        let properties = instance.get_physical_device_properties(physical_device);
+
+        PhysicalDeviceInfo {
+            name: device_name(&properties),
+            device_type: properties.device_type,
+            driver_info: DriverInfo::new(&properties),
+            properties,
+            memory_properties: instance.get_physical_device_memory_properties(physical_device),
+            features: instance.get_physical_device_features(physical_device),
+            is_suitable,
+        }
+    }
+}
+
+fn device_name(properties: &vk::PhysicalDeviceProperties) -> String {
+    unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+pub type PhysicalDeviceScorer = Box<dyn Fn(&PhysicalDeviceInfo) -> i64>;
 
 pub struct PhysicalDevice {
-    instance: Rc<VulkanInstance>,
+    instance: Shared<VulkanInstance>,
     physical_device: vk::PhysicalDevice,
-    queue_family: u32,
+    graphics_queue_family: u32,
+    present_queue_family: u32,
 }
 
 impl PhysicalDevice {
@@ -18,8 +120,76 @@ impl PhysicalDevice {
         self.physical_device
     }
 
-    pub fn get_queue_family(&self) -> u32 {
-        self.queue_family
+    pub fn get_graphics_queue_family(&self) -> u32 {
+        self.graphics_queue_family
+    }
+
+    pub fn get_present_queue_family(&self) -> u32 {
+        self.present_queue_family
+    }
+
+    pub fn supports_presentation(&self, surface: &Surface) -> bool {
+        surface.get_physical_device_surface_support(self.physical_device, self.present_queue_family)
+    }
+
+    pub fn driver_info(&self) -> DriverInfo {
+        let properties = self
+            .instance
+            .get_physical_device_properties(self.physical_device);
+
+        DriverInfo::new(&properties)
+    }
+
+    pub fn get_limits(&self) -> vk::PhysicalDeviceLimits {
+        self.instance
+            .get_physical_device_properties(self.physical_device)
+            .limits
+    }
+
+    /// Sum of the `DEVICE_LOCAL` heap sizes, a rough VRAM figure to pick quality presets from.
+    pub fn total_device_local_memory(&self) -> vk::DeviceSize {
+        let memory_properties = self
+            .instance
+            .get_physical_device_memory_properties(self.physical_device);
+
+        memory_properties
+            .memory_heaps
+            .iter()
+            .take(memory_properties.memory_heap_count as usize)
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum()
+    }
+
+    pub fn get_ray_tracing_properties(&self) -> vk::PhysicalDeviceRayTracingPropertiesNV {
+        unsafe { RayTracing::get_properties(self.instance.get(), self.physical_device) }
+    }
+
+    pub fn get_max_sampler_anisotropy(&self) -> f32 {
+        self.get_limits().max_sampler_anisotropy
+    }
+
+    pub fn get_max_sampler_lod_bias(&self) -> f32 {
+        self.get_limits().max_sampler_lod_bias
+    }
+
+    pub fn clamp_sample_count(&self, requested: vk::SampleCountFlags) -> vk::SampleCountFlags {
+        let limits = self.get_limits();
+        let supported =
+            limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+
+        [
+            vk::SampleCountFlags::TYPE_64,
+            vk::SampleCountFlags::TYPE_32,
+            vk::SampleCountFlags::TYPE_16,
+            vk::SampleCountFlags::TYPE_8,
+            vk::SampleCountFlags::TYPE_4,
+            vk::SampleCountFlags::TYPE_2,
+        ]
+        .iter()
+        .copied()
+        .find(|count| count.as_raw() <= requested.as_raw() && supported.contains(*count))
+        .unwrap_or(vk::SampleCountFlags::TYPE_1)
     }
 
     pub fn find_memory_type(
@@ -27,38 +197,47 @@ impl PhysicalDevice {
         type_filter: u32,
         properties: vk::MemoryPropertyFlags,
     ) -> Option<u32> {
-        let mem_properties = self
-            .instance
-            .get_physical_device_memory_properties(self.physical_device);
-
-        for i in 0..mem_properties.memory_type_count {
-            if type_filter & (1 << i) != 0
-                && mem_properties.memory_types[i as usize]
-                    .property_flags
-                    .contains(properties)
-            {
-                return Some(i);
-            }
-        }
+        self.instance
+            .find_memory_type(self.physical_device, type_filter, properties)
+    }
 
-        None
+    /// Looks for a memory type with `required | preferred` first, then settles for one that
+    /// only has `required`.
+    pub fn find_memory_type_with_fallback(
+        &self,
+        type_filter: u32,
+        required: vk::MemoryPropertyFlags,
+        preferred: vk::MemoryPropertyFlags,
+    ) -> Option<u32> {
+        self.find_memory_type(type_filter, required | preferred)
+            .or_else(|| self.find_memory_type(type_filter, required))
     }
 }
 
 pub struct PhysicalDeviceBuilder<'a> {
-    instance: Rc<VulkanInstance>,
+    instance: Shared<VulkanInstance>,
     surface: &'a Surface,
     extensions: Vec<DeviceExtensions>,
     features: Features,
+    scorer: Option<PhysicalDeviceScorer>,
+    queue_family: Option<u32>,
+    preferred_device_type: Option<vk::PhysicalDeviceType>,
+    device_name_filter: Option<String>,
+    physical_device_index: Option<usize>,
 }
 
 impl<'a> PhysicalDeviceBuilder<'a> {
-    pub fn new(instance: Rc<VulkanInstance>, surface: &'a Surface) -> Self {
+    pub fn new(instance: Shared<VulkanInstance>, surface: &'a Surface) -> Self {
         PhysicalDeviceBuilder {
             instance,
             surface,
             extensions: vec![],
             features: Features::default(),
+            scorer: None,
+            queue_family: None,
+            preferred_device_type: None,
+            device_name_filter: None,
+            physical_device_index: None,
         }
     }
 
@@ -72,32 +251,177 @@ impl<'a> PhysicalDeviceBuilder<'a> {
         self
     }
 
+    /// Picks the suitable device with the highest score instead of the first one found.
+    /// Ties go to the device enumerated first.
+    pub fn with_scorer(mut self, scorer: PhysicalDeviceScorer) -> Self {
+        self.scorer = Some(scorer);
+        self
+    }
+
+    /// Uses this queue family for both graphics and presentation instead of searching for one.
+    /// Devices where it lacks graphics or present support are not considered suitable.
+    pub fn with_queue_family(mut self, queue_family: u32) -> Self {
+        self.queue_family = Some(queue_family);
+        self
+    }
+
+    /// Only considers devices of this type when at least one suitable device has it.
+    pub fn with_preferred_device_type(mut self, device_type: vk::PhysicalDeviceType) -> Self {
+        self.preferred_device_type = Some(device_type);
+        self
+    }
+
+    /// Only considers devices whose name contains `name`, ignoring case.
+    pub fn with_device_name_filter(mut self, name: &str) -> Self {
+        self.device_name_filter = Some(name.to_lowercase());
+        self
+    }
+
+    /// Picks the device at this position in `list_physical_devices`, skipping the automatic
+    /// selection. Building fails if that device is not suitable.
+    pub fn with_physical_device_index(mut self, index: usize) -> Self {
+        self.physical_device_index = Some(index);
+        self
+    }
+
+    /// Every device the instance knows about, in enumeration order, with whether it meets the
+    /// requested extensions, features and queue family.
+    pub fn list_physical_devices(&self) -> Result<Vec<PhysicalDeviceInfo>, VulkanError> {
+        Ok(self
+            .instance
+            .enumerate_physical_devices()?
+            .into_iter()
+            .map(|device| {
+                let is_suitable = self.check_device(device).is_some();
+                PhysicalDeviceInfo::new(&self.instance, device, is_suitable)
+            })
+            .collect())
+    }
+
     pub fn build(self) -> Result<PhysicalDevice, VulkanError> {
         let physical_devices = self.instance.enumerate_physical_devices()?;
 
-        let (physical_device, queue_family) = physical_devices
+        if let Some(index) = self.physical_device_index {
+            let physical_device = *physical_devices.get(index).ok_or_else(|| {
+                VulkanError::PhysicalDeviceCreationError(format!(
+                    "No physical device at index {}",
+                    index
+                ))
+            })?;
+            let (graphics_queue_family, present_queue_family) =
+                self.check_device(physical_device).ok_or_else(|| {
+                    if self.lacks_presentation(physical_device) {
+                        VulkanError::PhysicalDeviceCreationError(format!(
+                            "Physical device at index {} cannot present to the surface",
+                            index
+                        ))
+                    } else {
+                        VulkanError::PhysicalDeviceCreationError(format!(
+                            "Physical device at index {} is not suitable",
+                            index
+                        ))
+                    }
+                })?;
+
+            return Ok(PhysicalDevice {
+                instance: self.instance,
+                physical_device,
+                graphics_queue_family,
+                present_queue_family,
+            });
+        }
+
+        let named_devices: Vec<vk::PhysicalDevice> = physical_devices
             .into_iter()
-            .find_map(|device| {
-                let queue_family = self.find_queue_family(device);
-                if self.is_device_suitable(device) && queue_family.is_some() {
-                    Some((device, queue_family.unwrap()))
-                } else {
-                    None
-                }
+            .filter(|device| self.matches_device_name(*device))
+            .collect();
+
+        let candidates: Vec<(vk::PhysicalDevice, (u32, u32))> = named_devices
+            .iter()
+            .copied()
+            .filter_map(|device| {
+                self.check_device(device)
+                    .map(|queue_families| (device, queue_families))
             })
+            .collect();
+        let candidates = self.prefer_device_type(candidates);
+
+        let selected = match &self.scorer {
+            Some(scorer) => candidates.into_iter().rev().max_by_key(|(device, _)| {
+                scorer(&PhysicalDeviceInfo::new(&self.instance, *device, true))
+            }),
+            None => candidates.into_iter().next(),
+        };
+
+        let (physical_device, (graphics_queue_family, present_queue_family)) = selected
             .ok_or_else(|| {
-                VulkanError::PhysicalDeviceCreationError(String::from(
-                    "Cannot find suitable physical device",
-                ))
+                // Tells headless machines apart from machines without any usable GPU.
+                if named_devices
+                    .iter()
+                    .any(|device| self.lacks_presentation(*device))
+                {
+                    VulkanError::PhysicalDeviceCreationError(String::from(
+                        "Found a graphics capable physical device, but none can present to the surface",
+                    ))
+                } else {
+                    VulkanError::PhysicalDeviceCreationError(String::from(
+                        "Cannot find suitable physical device",
+                    ))
+                }
             })?;
 
         Ok(PhysicalDevice {
             instance: self.instance,
             physical_device,
-            queue_family,
+            graphics_queue_family,
+            present_queue_family,
         })
     }
 
+    fn matches_device_name(&self, device: vk::PhysicalDevice) -> bool {
+        match &self.device_name_filter {
+            Some(name) => device_name(&self.instance.get_physical_device_properties(device))
+                .to_lowercase()
+                .contains(name.as_str()),
+            None => true,
+        }
+    }
+
+    fn prefer_device_type(
+        &self,
+        candidates: Vec<(vk::PhysicalDevice, (u32, u32))>,
+    ) -> Vec<(vk::PhysicalDevice, (u32, u32))> {
+        let preferred_device_type = match self.preferred_device_type {
+            Some(device_type) => device_type,
+            None => return candidates,
+        };
+
+        let preferred: Vec<(vk::PhysicalDevice, (u32, u32))> = candidates
+            .iter()
+            .copied()
+            .filter(|(device, _)| {
+                self.instance
+                    .get_physical_device_properties(*device)
+                    .device_type
+                    == preferred_device_type
+            })
+            .collect();
+
+        if preferred.is_empty() {
+            candidates
+        } else {
+            preferred
+        }
+    }
+
+    fn check_device(&self, device: vk::PhysicalDevice) -> Option<(u32, u32)> {
+        if self.is_device_suitable(device) {
+            self.select_queue_family(device)
+        } else {
+            None
+        }
+    }
+
     fn is_device_suitable(&self, device: vk::PhysicalDevice) -> bool {
         let swapchain_support = self.surface.query_swapchain_support(device).unwrap();
 
@@ -107,22 +431,86 @@ impl<'a> PhysicalDeviceBuilder<'a> {
             && !swapchain_support.present_modes.is_empty()
     }
 
-    fn find_queue_family(&self, device: vk::PhysicalDevice) -> Option<u32> {
+    fn select_queue_family(&self, device: vk::PhysicalDevice) -> Option<(u32, u32)> {
+        match self.queue_family {
+            Some(queue_family) if self.check_queue_family_support(device, queue_family) => {
+                Some((queue_family, queue_family))
+            }
+            Some(_) => None,
+            None => self.find_queue_family(device),
+        }
+    }
+
+    fn check_queue_family_support(&self, device: vk::PhysicalDevice, index: u32) -> bool {
         let queue_families = self
             .instance
             .get_physical_device_queue_family_properties(device);
 
-        for (index, queue_family) in queue_families.iter().enumerate() {
-            if queue_family.queue_count > 0
+        match queue_families.get(index as usize) {
+            Some(queue_family) => {
+                queue_family.queue_count > 0
+                    && queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+                    && self
+                        .surface
+                        .get_physical_device_surface_support(device, index)
+            }
+            None => false,
+        }
+    }
+
+    fn lacks_presentation(&self, device: vk::PhysicalDevice) -> bool {
+        let queue_families = self
+            .instance
+            .get_physical_device_queue_family_properties(device);
+
+        let has_graphics = queue_families.iter().any(|queue_family| {
+            queue_family.queue_count > 0
                 && queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-                && self
-                    .surface
-                    .get_physical_device_surface_support(device, index as u32)
-            {
-                return Some(index as u32);
+        });
+        let has_present = (0..queue_families.len() as u32).any(|index| {
+            self.surface
+                .get_physical_device_surface_support(device, index)
+        });
+
+        has_graphics && !has_present
+    }
+
+    fn find_queue_family(&self, device: vk::PhysicalDevice) -> Option<(u32, u32)> {
+        let queue_families = self
+            .instance
+            .get_physical_device_queue_family_properties(device);
+
+        let mut graphics_family = None;
+        let mut present_family = None;
+
+        for (index, queue_family) in queue_families.iter().enumerate() {
+            if queue_family.queue_count == 0 {
+                continue;
+            }
+
+            let index = index as u32;
+            let supports_graphics = queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS);
+            let supports_present = self
+                .surface
+                .get_physical_device_surface_support(device, index);
+
+            if supports_graphics && supports_present {
+                return Some((index, index));
+            }
+            if supports_graphics && graphics_family.is_none() {
+                graphics_family = Some(index);
+            }
+            if supports_present && present_family.is_none() {
+                present_family = Some(index);
+            }
+        }
+
+        match (graphics_family, present_family) {
+            (Some(graphics_family), Some(present_family)) => {
+                Some((graphics_family, present_family))
             }
+            _ => None,
         }
-        None
     }
 
     fn check_device_extensions_support(&self, device: vk::PhysicalDevice) -> bool {
@@ -154,5 +542,20 @@ impl<'a> PhysicalDeviceBuilder<'a> {
                 || available_features.tessellation_shader == vk::TRUE)
             && (!self.features.fragment_stores_and_atomics
                 || available_features.fragment_stores_and_atomics == vk::TRUE)
+            && (!self.features.depth_clamp || available_features.depth_clamp == vk::TRUE)
+            && (!self.features.pipeline_statistics_query
+                || available_features.pipeline_statistics_query == vk::TRUE)
+            && (!self.features.shader_int64 || available_features.shader_int64 == vk::TRUE)
+            && (!self.features.independent_blend
+                || available_features.independent_blend == vk::TRUE)
+            && (!self.features.wide_lines || available_features.wide_lines == vk::TRUE)
+            && (!self.features.fill_mode_non_solid
+                || available_features.fill_mode_non_solid == vk::TRUE)
+            && (!self.features.multiview
+                || self
+                    .instance
+                    .get_physical_device_multiview_features(device)
+                    .multiview
+                    == vk::TRUE)
     }
 }
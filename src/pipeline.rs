@@ -0,0 +1,780 @@
+use std::ffi::CString;
+use std::os::raw::c_void;
+
+use ash::vk;
+
+use crate::buffer::{Buffer, BufferBuilder, BufferType};
+use crate::device::VulkanDevice;
+use crate::errors::VulkanError;
+use crate::shader_module::ShaderModule;
+use crate::vulkan_context::VulkanContext;
+use crate::Shared;
+
+pub struct Pipeline {
+    device: Shared<VulkanDevice>,
+    pipeline: vk::Pipeline,
+    bind_point: vk::PipelineBindPoint,
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.device.destroy_pipeline(self.pipeline);
+    }
+}
+
+impl Pipeline {
+    pub fn get(&self) -> vk::Pipeline {
+        self.pipeline
+    }
+
+    pub fn get_bind_point(&self) -> vk::PipelineBindPoint {
+        self.bind_point
+    }
+
+    pub fn bind(&self, command_buffer: vk::CommandBuffer) {
+        self.device
+            .cmd_bind_pipeline(command_buffer, self.bind_point, self.pipeline);
+    }
+}
+
+pub struct PipelineLayout {
+    device: Shared<VulkanDevice>,
+    pipeline_layout: vk::PipelineLayout,
+}
+
+impl Drop for PipelineLayout {
+    fn drop(&mut self) {
+        self.device.destroy_pipeline_layout(self.pipeline_layout);
+    }
+}
+
+impl PipelineLayout {
+    pub fn get(&self) -> vk::PipelineLayout {
+        self.pipeline_layout
+    }
+}
+
+pub struct PipelineLayoutBuilder<'a> {
+    context: &'a VulkanContext,
+    set_layouts: Vec<vk::DescriptorSetLayout>,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+}
+
+impl<'a> PipelineLayoutBuilder<'a> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        PipelineLayoutBuilder {
+            context,
+            set_layouts: vec![],
+            push_constant_ranges: vec![],
+        }
+    }
+
+    pub fn with_set_layout(mut self, set_layout: vk::DescriptorSetLayout) -> Self {
+        self.set_layouts.push(set_layout);
+        self
+    }
+
+    pub fn with_push_constant_range(
+        mut self,
+        stage_flags: vk::ShaderStageFlags,
+        offset: u32,
+        size: u32,
+    ) -> Self {
+        self.push_constant_ranges.push(
+            vk::PushConstantRange::builder()
+                .stage_flags(stage_flags)
+                .offset(offset)
+                .size(size)
+                .build(),
+        );
+        self
+    }
+
+    pub fn build(self) -> Result<PipelineLayout, VulkanError> {
+        let device = self.context.get_device();
+        let max_push_constants_size = device.get_max_push_constants_size();
+
+        if let Some(range) = self
+            .push_constant_ranges
+            .iter()
+            .find(|range| range.offset + range.size > max_push_constants_size)
+        {
+            return Err(VulkanError::PipelineError(format!(
+                "Push constant range {}..{} exceeds maxPushConstantsSize ({})",
+                range.offset,
+                range.offset + range.size,
+                max_push_constants_size
+            )));
+        }
+
+        let layout_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&self.set_layouts)
+            .push_constant_ranges(&self.push_constant_ranges)
+            .build();
+
+        let pipeline_layout = device.create_pipeline_layout(&layout_info)?;
+
+        Ok(PipelineLayout {
+            device: Shared::clone(device),
+            pipeline_layout,
+        })
+    }
+}
+
+pub struct ComputePipelineBuilder<'a> {
+    context: &'a VulkanContext,
+    shader_module: Option<&'a ShaderModule>,
+    pipeline_layout: vk::PipelineLayout,
+    entry_point: &'a str,
+}
+
+impl<'a> ComputePipelineBuilder<'a> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        ComputePipelineBuilder {
+            context,
+            shader_module: None,
+            pipeline_layout: vk::PipelineLayout::null(),
+            entry_point: "main",
+        }
+    }
+
+    pub fn with_shader_module(mut self, shader_module: &'a ShaderModule) -> Self {
+        self.shader_module = Some(shader_module);
+        self
+    }
+
+    pub fn with_pipeline_layout(mut self, pipeline_layout: vk::PipelineLayout) -> Self {
+        self.pipeline_layout = pipeline_layout;
+        self
+    }
+
+    pub fn with_entry_point(mut self, entry_point: &'a str) -> Self {
+        self.entry_point = entry_point;
+        self
+    }
+
+    pub fn build(self) -> Result<Pipeline, VulkanError> {
+        let shader_module = self.shader_module.ok_or_else(|| {
+            VulkanError::PipelineError(String::from("Compute shader module not provided"))
+        })?;
+
+        if self.pipeline_layout == vk::PipelineLayout::null() {
+            return Err(VulkanError::PipelineError(String::from(
+                "Pipeline layout not provided",
+            )));
+        }
+
+        let entry_point = CString::new(self.entry_point)
+            .map_err(|err| VulkanError::PipelineError(err.to_string()))?;
+
+        let stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(shader_module.get())
+            .name(&entry_point)
+            .build();
+
+        let info = vk::ComputePipelineCreateInfo::builder()
+            .stage(stage)
+            .layout(self.pipeline_layout)
+            .build();
+
+        let device = self.context.get_device();
+        let pipeline = device.create_compute_pipelines(&[info])?[0];
+
+        Ok(Pipeline {
+            device: Shared::clone(device),
+            pipeline,
+            bind_point: vk::PipelineBindPoint::COMPUTE,
+        })
+    }
+}
+
+pub struct ShaderBindingTable {
+    buffer: Buffer,
+    raygen_offset: vk::DeviceSize,
+    miss_offset: vk::DeviceSize,
+    hit_offset: vk::DeviceSize,
+    stride: vk::DeviceSize,
+}
+
+impl ShaderBindingTable {
+    pub fn get_buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn get_raygen_offset(&self) -> vk::DeviceSize {
+        self.raygen_offset
+    }
+
+    pub fn get_miss_offset(&self) -> vk::DeviceSize {
+        self.miss_offset
+    }
+
+    pub fn get_hit_offset(&self) -> vk::DeviceSize {
+        self.hit_offset
+    }
+
+    pub fn get_stride(&self) -> vk::DeviceSize {
+        self.stride
+    }
+}
+
+pub struct RayTracingPipeline {
+    pipeline: Pipeline,
+    shader_binding_table: ShaderBindingTable,
+}
+
+impl RayTracingPipeline {
+    pub fn get_pipeline(&self) -> &Pipeline {
+        &self.pipeline
+    }
+
+    pub fn get_shader_binding_table(&self) -> &ShaderBindingTable {
+        &self.shader_binding_table
+    }
+
+    pub fn trace_rays(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        width: u32,
+        height: u32,
+        depth: u32,
+    ) -> Result<(), VulkanError> {
+        let table = &self.shader_binding_table;
+        self.pipeline.device.cmd_trace_rays(
+            command_buffer,
+            table.buffer.get(),
+            table.raygen_offset,
+            table.miss_offset,
+            table.hit_offset,
+            table.stride,
+            width,
+            height,
+            depth,
+        )
+    }
+}
+
+pub struct RayTracingPipelineBuilder<'a> {
+    context: &'a VulkanContext,
+    raygen_shader: Option<&'a ShaderModule>,
+    miss_shader: Option<&'a ShaderModule>,
+    closest_hit_shader: Option<&'a ShaderModule>,
+    pipeline_layout: vk::PipelineLayout,
+    max_recursion_depth: u32,
+}
+
+impl<'a> RayTracingPipelineBuilder<'a> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        RayTracingPipelineBuilder {
+            context,
+            raygen_shader: None,
+            miss_shader: None,
+            closest_hit_shader: None,
+            pipeline_layout: vk::PipelineLayout::null(),
+            max_recursion_depth: 1,
+        }
+    }
+
+    pub fn with_raygen_shader(mut self, raygen_shader: &'a ShaderModule) -> Self {
+        self.raygen_shader = Some(raygen_shader);
+        self
+    }
+
+    pub fn with_miss_shader(mut self, miss_shader: &'a ShaderModule) -> Self {
+        self.miss_shader = Some(miss_shader);
+        self
+    }
+
+    pub fn with_closest_hit_shader(mut self, closest_hit_shader: &'a ShaderModule) -> Self {
+        self.closest_hit_shader = Some(closest_hit_shader);
+        self
+    }
+
+    pub fn with_pipeline_layout(mut self, pipeline_layout: vk::PipelineLayout) -> Self {
+        self.pipeline_layout = pipeline_layout;
+        self
+    }
+
+    pub fn with_max_recursion_depth(mut self, max_recursion_depth: u32) -> Self {
+        self.max_recursion_depth = max_recursion_depth;
+        self
+    }
+
+    pub fn build(self) -> Result<RayTracingPipeline, VulkanError> {
+        let (raygen_shader, miss_shader, closest_hit_shader) = match (
+            self.raygen_shader,
+            self.miss_shader,
+            self.closest_hit_shader,
+        ) {
+            (Some(raygen), Some(miss), Some(closest_hit)) => (raygen, miss, closest_hit),
+            _ => {
+                return Err(VulkanError::PipelineError(String::from(
+                    "Raygen, miss and closest hit shaders must all be provided",
+                )))
+            }
+        };
+
+        if self.pipeline_layout == vk::PipelineLayout::null() {
+            return Err(VulkanError::PipelineError(String::from(
+                "Pipeline layout not provided",
+            )));
+        }
+
+        let entry_point = CString::new("main").unwrap();
+
+        let stages = [
+            (vk::ShaderStageFlags::RAYGEN_NV, raygen_shader),
+            (vk::ShaderStageFlags::MISS_NV, miss_shader),
+            (vk::ShaderStageFlags::CLOSEST_HIT_NV, closest_hit_shader),
+        ]
+        .iter()
+        .map(|(stage, shader)| {
+            vk::PipelineShaderStageCreateInfo::builder()
+                .stage(*stage)
+                .module(shader.get())
+                .name(&entry_point)
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+        let groups = [
+            vk::RayTracingShaderGroupCreateInfoNV::builder()
+                .ty(vk::RayTracingShaderGroupTypeNV::GENERAL)
+                .general_shader(0)
+                .closest_hit_shader(vk::SHADER_UNUSED_NV)
+                .any_hit_shader(vk::SHADER_UNUSED_NV)
+                .intersection_shader(vk::SHADER_UNUSED_NV)
+                .build(),
+            vk::RayTracingShaderGroupCreateInfoNV::builder()
+                .ty(vk::RayTracingShaderGroupTypeNV::GENERAL)
+                .general_shader(1)
+                .closest_hit_shader(vk::SHADER_UNUSED_NV)
+                .any_hit_shader(vk::SHADER_UNUSED_NV)
+                .intersection_shader(vk::SHADER_UNUSED_NV)
+                .build(),
+            vk::RayTracingShaderGroupCreateInfoNV::builder()
+                .ty(vk::RayTracingShaderGroupTypeNV::TRIANGLES_HIT_GROUP)
+                .general_shader(vk::SHADER_UNUSED_NV)
+                .closest_hit_shader(2)
+                .any_hit_shader(vk::SHADER_UNUSED_NV)
+                .intersection_shader(vk::SHADER_UNUSED_NV)
+                .build(),
+        ];
+
+        let info = vk::RayTracingPipelineCreateInfoNV::builder()
+            .stages(&stages)
+            .groups(&groups)
+            .max_recursion_depth(self.max_recursion_depth)
+            .layout(self.pipeline_layout)
+            .build();
+
+        let device = self.context.get_device();
+        let pipeline = Pipeline {
+            device: Shared::clone(device),
+            pipeline: device.create_ray_tracing_pipelines(&[info])?[0],
+            bind_point: vk::PipelineBindPoint::RAY_TRACING_NV,
+        };
+
+        let shader_binding_table = self.build_shader_binding_table(pipeline.get(), groups.len())?;
+
+        Ok(RayTracingPipeline {
+            pipeline,
+            shader_binding_table,
+        })
+    }
+
+    fn build_shader_binding_table(
+        &self,
+        pipeline: vk::Pipeline,
+        group_count: usize,
+    ) -> Result<ShaderBindingTable, VulkanError> {
+        let properties = self
+            .context
+            .get_physical_device()
+            .get_ray_tracing_properties();
+        let handle_size = properties.shader_group_handle_size as usize;
+        let stride = properties.shader_group_base_alignment.max(1) as usize;
+
+        let mut handles = vec![0u8; handle_size * group_count];
+        self.context
+            .get_device()
+            .get_ray_tracing_shader_group_handles(pipeline, 0, group_count as u32, &mut handles)?;
+
+        // Each group record starts on a base-aligned offset so any of them can be a table start.
+        let mut table = vec![0u8; stride * group_count];
+        for (group, handle) in handles.chunks(handle_size).enumerate() {
+            table[group * stride..group * stride + handle_size].copy_from_slice(handle);
+        }
+
+        let buffer = BufferBuilder::new(self.context)
+            .with_type(BufferType::ShaderBindingTable)
+            .with_size(table.len() as vk::DeviceSize)
+            .build()?;
+        buffer.copy_data(table.as_ptr() as *const c_void)?;
+
+        let stride = stride as vk::DeviceSize;
+        Ok(ShaderBindingTable {
+            buffer,
+            raygen_offset: 0,
+            miss_offset: stride,
+            hit_offset: 2 * stride,
+            stride,
+        })
+    }
+}
+
+pub struct GraphicsPipelineBuilder<'a> {
+    context: &'a VulkanContext,
+    vertex_shader: Option<&'a ShaderModule>,
+    fragment_shader: Option<&'a ShaderModule>,
+    geometry_shader: Option<&'a ShaderModule>,
+    tessellation_shaders: Option<(&'a ShaderModule, &'a ShaderModule)>,
+    patch_control_points: u32,
+    pipeline_layout: vk::PipelineLayout,
+    render_pass: Option<vk::RenderPass>,
+    subpass: u32,
+    vertex_bindings: Vec<vk::VertexInputBindingDescription>,
+    vertex_attributes: Vec<vk::VertexInputAttributeDescription>,
+    topology: vk::PrimitiveTopology,
+    cull_mode: vk::CullModeFlags,
+    front_face: vk::FrontFace,
+    depth_test: bool,
+    depth_clamp: bool,
+    rasterizer_discard: bool,
+    polygon_mode: vk::PolygonMode,
+    line_width: f32,
+    samples: Option<vk::SampleCountFlags>,
+    color_attachment_count: u32,
+    dynamic_states: Vec<vk::DynamicState>,
+}
+
+impl<'a> GraphicsPipelineBuilder<'a> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        GraphicsPipelineBuilder {
+            context,
+            vertex_shader: None,
+            fragment_shader: None,
+            geometry_shader: None,
+            tessellation_shaders: None,
+            patch_control_points: 3,
+            pipeline_layout: vk::PipelineLayout::null(),
+            render_pass: None,
+            subpass: 0,
+            vertex_bindings: vec![],
+            vertex_attributes: vec![],
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            cull_mode: vk::CullModeFlags::BACK,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            depth_test: true,
+            depth_clamp: false,
+            rasterizer_discard: false,
+            polygon_mode: vk::PolygonMode::FILL,
+            line_width: 1.0,
+            samples: None,
+            color_attachment_count: 1,
+            dynamic_states: vec![],
+        }
+    }
+
+    pub fn with_vertex_shader(mut self, vertex_shader: &'a ShaderModule) -> Self {
+        self.vertex_shader = Some(vertex_shader);
+        self
+    }
+
+    pub fn with_fragment_shader(mut self, fragment_shader: &'a ShaderModule) -> Self {
+        self.fragment_shader = Some(fragment_shader);
+        self
+    }
+
+    /// Requires `Features::geometry_shader` to be enabled on the context.
+    pub fn with_geometry_shader(mut self, geometry_shader: &'a ShaderModule) -> Self {
+        self.geometry_shader = Some(geometry_shader);
+        self
+    }
+
+    /// Requires `Features::tessellation_shader` to be enabled on the context. The input
+    /// topology becomes `PATCH_LIST` with the given number of control points per patch.
+    pub fn with_tessellation_shaders(
+        mut self,
+        control_shader: &'a ShaderModule,
+        evaluation_shader: &'a ShaderModule,
+        patch_control_points: u32,
+    ) -> Self {
+        self.tessellation_shaders = Some((control_shader, evaluation_shader));
+        self.patch_control_points = patch_control_points;
+        self
+    }
+
+    pub fn with_pipeline_layout(mut self, pipeline_layout: vk::PipelineLayout) -> Self {
+        self.pipeline_layout = pipeline_layout;
+        self
+    }
+
+    pub fn with_render_pass(mut self, render_pass: vk::RenderPass, subpass: u32) -> Self {
+        self.render_pass = Some(render_pass);
+        self.subpass = subpass;
+        self
+    }
+
+    pub fn with_vertex_input(
+        mut self,
+        bindings: &[vk::VertexInputBindingDescription],
+        attributes: &[vk::VertexInputAttributeDescription],
+    ) -> Self {
+        self.vertex_bindings = bindings.to_vec();
+        self.vertex_attributes = attributes.to_vec();
+        self
+    }
+
+    pub fn with_topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn with_cull_mode(mut self, cull_mode: vk::CullModeFlags) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn with_front_face(mut self, front_face: vk::FrontFace) -> Self {
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn with_depth_test(mut self, depth_test: bool) -> Self {
+        self.depth_test = depth_test;
+        self
+    }
+
+    /// Requires `Features::depth_clamp` to be enabled on the context.
+    pub fn with_depth_clamp(mut self, depth_clamp: bool) -> Self {
+        self.depth_clamp = depth_clamp;
+        self
+    }
+
+    pub fn with_rasterizer_discard(mut self, rasterizer_discard: bool) -> Self {
+        self.rasterizer_discard = rasterizer_discard;
+        self
+    }
+
+    /// Modes other than `FILL`, e.g. `LINE` for wireframe, require
+    /// `Features::fill_mode_non_solid` to be enabled on the context.
+    pub fn with_polygon_mode(mut self, polygon_mode: vk::PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    /// Widths other than 1.0 require `Features::wide_lines` to be enabled on the context.
+    pub fn with_line_width(mut self, line_width: f32) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    /// Defaults to the context sample count; must match the render pass attachments.
+    pub fn with_samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = Some(samples);
+        self
+    }
+
+    /// Number of color attachments of the subpass, each given the same blend state.
+    pub fn with_color_attachment_count(mut self, color_attachment_count: u32) -> Self {
+        self.color_attachment_count = color_attachment_count;
+        self
+    }
+
+    pub fn with_dynamic_states(mut self, dynamic_states: &[vk::DynamicState]) -> Self {
+        self.dynamic_states = dynamic_states.to_vec();
+        self
+    }
+
+    pub fn build(self) -> Result<Pipeline, VulkanError> {
+        let vertex_shader = self.vertex_shader.ok_or_else(|| {
+            VulkanError::PipelineError(String::from("Vertex shader module not provided"))
+        })?;
+
+        self.check_rasterization_state()?;
+
+        let max_color_attachments = self
+            .context
+            .get_physical_device()
+            .get_limits()
+            .max_color_attachments;
+        if self.color_attachment_count > max_color_attachments {
+            return Err(VulkanError::PipelineError(format!(
+                "{} color attachments requested, the device supports {}",
+                self.color_attachment_count, max_color_attachments
+            )));
+        }
+
+        if self.pipeline_layout == vk::PipelineLayout::null() {
+            return Err(VulkanError::PipelineError(String::from(
+                "Pipeline layout not provided",
+            )));
+        }
+
+        let entry_point = CString::new("main").unwrap();
+
+        let mut modules = vec![(vk::ShaderStageFlags::VERTEX, vertex_shader)];
+
+        if let Some((control_shader, evaluation_shader)) = self.tessellation_shaders {
+            modules.push((vk::ShaderStageFlags::TESSELLATION_CONTROL, control_shader));
+            modules.push((
+                vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+                evaluation_shader,
+            ));
+        }
+
+        if let Some(geometry_shader) = self.geometry_shader {
+            modules.push((vk::ShaderStageFlags::GEOMETRY, geometry_shader));
+        }
+
+        if let Some(fragment_shader) = self.fragment_shader {
+            modules.push((vk::ShaderStageFlags::FRAGMENT, fragment_shader));
+        }
+
+        let stages: Vec<vk::PipelineShaderStageCreateInfo> = modules
+            .into_iter()
+            .map(|(stage, module)| {
+                vk::PipelineShaderStageCreateInfo::builder()
+                    .stage(stage)
+                    .module(module.get())
+                    .name(&entry_point)
+                    .build()
+            })
+            .collect();
+
+        let topology = if self.tessellation_shaders.is_some() {
+            vk::PrimitiveTopology::PATCH_LIST
+        } else {
+            self.topology
+        };
+
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_binding_descriptions(&self.vertex_bindings)
+            .vertex_attribute_descriptions(&self.vertex_attributes)
+            .build();
+
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
+            .topology(topology)
+            .primitive_restart_enable(false)
+            .build();
+
+        let tessellation_state = vk::PipelineTessellationStateCreateInfo::builder()
+            .patch_control_points(self.patch_control_points)
+            .build();
+
+        let viewports = [self.context.default_viewport()];
+        let scissors = [self.context.default_scissor()];
+        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewports(&viewports)
+            .scissors(&scissors)
+            .build();
+
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
+            .depth_clamp_enable(self.depth_clamp)
+            .rasterizer_discard_enable(self.rasterizer_discard)
+            .polygon_mode(self.polygon_mode)
+            .line_width(self.line_width)
+            .cull_mode(self.cull_mode)
+            .front_face(self.front_face)
+            .depth_bias_enable(false)
+            .build();
+
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
+            .rasterization_samples(
+                self.samples
+                    .unwrap_or_else(|| self.context.get_sample_count()),
+            )
+            .sample_shading_enable(false)
+            .build();
+
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
+            .depth_test_enable(self.depth_test)
+            .depth_write_enable(self.depth_test)
+            .depth_compare_op(vk::CompareOp::LESS)
+            .depth_bounds_test_enable(false)
+            .stencil_test_enable(false)
+            .build();
+
+        let color_blend_attachment = vk::PipelineColorBlendAttachmentState::builder()
+            .color_write_mask(vk::ColorComponentFlags::all())
+            .blend_enable(false)
+            .build();
+        let color_blend_attachments =
+            vec![color_blend_attachment; self.color_attachment_count as usize];
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .logic_op_enable(false)
+            .attachments(&color_blend_attachments)
+            .build();
+
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&self.dynamic_states)
+            .build();
+
+        let render_pass = self
+            .render_pass
+            .unwrap_or_else(|| self.context.get_current_render_pass().get());
+
+        let mut info = vk::GraphicsPipelineCreateInfo::builder()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state)
+            .layout(self.pipeline_layout)
+            .render_pass(render_pass)
+            .subpass(self.subpass);
+
+        if self.tessellation_shaders.is_some() {
+            info = info.tessellation_state(&tessellation_state);
+        }
+
+        let info = info.build();
+
+        let device = self.context.get_device();
+        let pipeline = device.create_graphics_pipelines(&[info])?[0];
+
+        Ok(Pipeline {
+            device: Shared::clone(device),
+            pipeline,
+            bind_point: vk::PipelineBindPoint::GRAPHICS,
+        })
+    }
+
+    fn check_rasterization_state(&self) -> Result<(), VulkanError> {
+        let features = self.context.get_device().enabled_features();
+
+        if self.polygon_mode != vk::PolygonMode::FILL && !features.fill_mode_non_solid {
+            return Err(VulkanError::PipelineError(String::from(
+                "Polygon modes other than FILL require the fill_mode_non_solid feature",
+            )));
+        }
+
+        if (self.line_width - 1.0).abs() > f32::EPSILON {
+            if !features.wide_lines {
+                return Err(VulkanError::PipelineError(String::from(
+                    "Line widths other than 1.0 require the wide_lines feature",
+                )));
+            }
+
+            let [min_width, max_width] = self
+                .context
+                .get_physical_device()
+                .get_limits()
+                .line_width_range;
+            if self.line_width < min_width || self.line_width > max_width {
+                return Err(VulkanError::PipelineError(format!(
+                    "Line width {} is out of the supported range [{}, {}]",
+                    self.line_width, min_width, max_width
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+use ash::vk;
+
+use crate::device::VulkanDevice;
+use crate::errors::VulkanError;
+use crate::vulkan_context::VulkanContext;
+use crate::Shared;
+
+const PIPELINE_STATISTICS: [vk::QueryPipelineStatisticFlags; 11] = [
+    vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_VERTICES,
+    vk::QueryPipelineStatisticFlags::INPUT_ASSEMBLY_PRIMITIVES,
+    vk::QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS,
+    vk::QueryPipelineStatisticFlags::GEOMETRY_SHADER_INVOCATIONS,
+    vk::QueryPipelineStatisticFlags::GEOMETRY_SHADER_PRIMITIVES,
+    vk::QueryPipelineStatisticFlags::CLIPPING_INVOCATIONS,
+    vk::QueryPipelineStatisticFlags::CLIPPING_PRIMITIVES,
+    vk::QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS,
+    vk::QueryPipelineStatisticFlags::TESSELLATION_CONTROL_SHADER_PATCHES,
+    vk::QueryPipelineStatisticFlags::TESSELLATION_EVALUATION_SHADER_INVOCATIONS,
+    vk::QueryPipelineStatisticFlags::COMPUTE_SHADER_INVOCATIONS,
+];
+
+/// Counters of a pipeline statistics query, `None` for the statistics the pool was not
+/// created with.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PipelineStatistics {
+    pub input_assembly_vertices: Option<u64>,
+    pub input_assembly_primitives: Option<u64>,
+    pub vertex_shader_invocations: Option<u64>,
+    pub geometry_shader_invocations: Option<u64>,
+    pub geometry_shader_primitives: Option<u64>,
+    pub clipping_invocations: Option<u64>,
+    pub clipping_primitives: Option<u64>,
+    pub fragment_shader_invocations: Option<u64>,
+    pub tessellation_control_shader_patches: Option<u64>,
+    pub tessellation_evaluation_shader_invocations: Option<u64>,
+    pub compute_shader_invocations: Option<u64>,
+}
+
+impl PipelineStatistics {
+    fn from_values(statistics: vk::QueryPipelineStatisticFlags, values: &[u64]) -> Self {
+        // The values are written in the order of the flag bits, skipping the disabled ones.
+        let mut values = values.iter().copied();
+        let mut counters = PIPELINE_STATISTICS.iter().map(|flag| {
+            if statistics.contains(*flag) {
+                values.next()
+            } else {
+                None
+            }
+        });
+        let mut next = || counters.next().flatten();
+
+        PipelineStatistics {
+            input_assembly_vertices: next(),
+            input_assembly_primitives: next(),
+            vertex_shader_invocations: next(),
+            geometry_shader_invocations: next(),
+            geometry_shader_primitives: next(),
+            clipping_invocations: next(),
+            clipping_primitives: next(),
+            fragment_shader_invocations: next(),
+            tessellation_control_shader_patches: next(),
+            tessellation_evaluation_shader_invocations: next(),
+            compute_shader_invocations: next(),
+        }
+    }
+}
+
+pub struct QueryPool {
+    device: Shared<VulkanDevice>,
+    query_pool: vk::QueryPool,
+    query_type: vk::QueryType,
+    query_count: u32,
+    pipeline_statistics: vk::QueryPipelineStatisticFlags,
+}
+
+impl Drop for QueryPool {
+    fn drop(&mut self) {
+        self.device.destroy_query_pool(self.query_pool);
+    }
+}
+
+impl QueryPool {
+    pub fn get(&self) -> vk::QueryPool {
+        self.query_pool
+    }
+
+    pub fn get_query_type(&self) -> vk::QueryType {
+        self.query_type
+    }
+
+    pub fn get_query_count(&self) -> u32 {
+        self.query_count
+    }
+
+    /// Must be recorded outside of a render pass before the queries are used again.
+    pub fn reset(&self, command_buffer: vk::CommandBuffer) {
+        self.device
+            .cmd_reset_query_pool(command_buffer, self.query_pool, 0, self.query_count);
+    }
+
+    pub fn begin(&self, command_buffer: vk::CommandBuffer, query: u32) {
+        self.device.cmd_begin_query(
+            command_buffer,
+            self.query_pool,
+            query,
+            vk::QueryControlFlags::empty(),
+        );
+    }
+
+    pub fn end(&self, command_buffer: vk::CommandBuffer, query: u32) {
+        self.device
+            .cmd_end_query(command_buffer, self.query_pool, query);
+    }
+
+    /// Waits for the results of occlusion or timestamp queries.
+    pub fn get_results(&self, first_query: u32, query_count: u32) -> Result<Vec<u64>, VulkanError> {
+        if self.query_type == vk::QueryType::PIPELINE_STATISTICS {
+            return Err(VulkanError::QueryPoolError(String::from(
+                "Use get_pipeline_statistics for pipeline statistics queries",
+            )));
+        }
+
+        let mut results = vec![0; query_count as usize];
+        self.device.get_query_pool_results(
+            self.query_pool,
+            first_query,
+            query_count,
+            1,
+            &mut results,
+            vk::QueryResultFlags::WAIT,
+        )?;
+
+        Ok(results)
+    }
+
+    /// Waits for the results of pipeline statistics queries.
+    pub fn get_pipeline_statistics(
+        &self,
+        first_query: u32,
+        query_count: u32,
+    ) -> Result<Vec<PipelineStatistics>, VulkanError> {
+        if self.query_type != vk::QueryType::PIPELINE_STATISTICS {
+            return Err(VulkanError::QueryPoolError(String::from(
+                "Query pool was not created for pipeline statistics",
+            )));
+        }
+
+        let stride = PIPELINE_STATISTICS
+            .iter()
+            .filter(|flag| self.pipeline_statistics.contains(**flag))
+            .count();
+        let mut values = vec![0; query_count as usize * stride];
+        self.device.get_query_pool_results(
+            self.query_pool,
+            first_query,
+            query_count,
+            stride,
+            &mut values,
+            vk::QueryResultFlags::WAIT,
+        )?;
+
+        Ok(values
+            .chunks(stride)
+            .map(|values| PipelineStatistics::from_values(self.pipeline_statistics, values))
+            .collect())
+    }
+}
+
+pub struct QueryPoolBuilder<'a> {
+    context: &'a VulkanContext,
+    query_type: vk::QueryType,
+    query_count: u32,
+    pipeline_statistics: vk::QueryPipelineStatisticFlags,
+}
+
+impl<'a> QueryPoolBuilder<'a> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        QueryPoolBuilder {
+            context,
+            query_type: vk::QueryType::OCCLUSION,
+            query_count: 1,
+            pipeline_statistics: vk::QueryPipelineStatisticFlags::empty(),
+        }
+    }
+
+    pub fn with_query_type(mut self, query_type: vk::QueryType) -> Self {
+        self.query_type = query_type;
+        self
+    }
+
+    pub fn with_query_count(mut self, query_count: u32) -> Self {
+        self.query_count = query_count;
+        self
+    }
+
+    /// Makes a pipeline statistics pool, which needs the `pipeline_statistics_query` feature.
+    pub fn with_pipeline_statistics(mut self, statistics: vk::QueryPipelineStatisticFlags) -> Self {
+        self.query_type = vk::QueryType::PIPELINE_STATISTICS;
+        self.pipeline_statistics = statistics;
+        self
+    }
+
+    pub fn build(self) -> Result<QueryPool, VulkanError> {
+        let device = self.context.get_device();
+
+        if self.query_type == vk::QueryType::PIPELINE_STATISTICS {
+            if !device.enabled_features().pipeline_statistics_query {
+                return Err(VulkanError::QueryPoolError(String::from(
+                    "Pipeline statistics queries need the pipeline_statistics_query feature",
+                )));
+            }
+            if self.pipeline_statistics.is_empty() {
+                return Err(VulkanError::QueryPoolError(String::from(
+                    "No pipeline statistics selected",
+                )));
+            }
+        }
+
+        let pool_info = vk::QueryPoolCreateInfo::builder()
+            .query_type(self.query_type)
+            .query_count(self.query_count)
+            .pipeline_statistics(self.pipeline_statistics)
+            .build();
+        let query_pool = device.create_query_pool(&pool_info)?;
+
+        Ok(QueryPool {
+            device: Shared::clone(device),
+            query_pool,
+            query_type: self.query_type,
+            query_count: self.query_count,
+            pipeline_statistics: self.pipeline_statistics,
+        })
+    }
+}
@@ -1,13 +1,12 @@
-use std::rc::Rc;
-
 use ash::vk;
 
 use crate::device::VulkanDevice;
 use crate::errors::VulkanError;
 use crate::vulkan_context::VulkanContext;
+use crate::Shared;
 
 pub struct RenderPass {
-    device: Rc<VulkanDevice>,
+    device: Shared<VulkanDevice>,
     render_pass: vk::RenderPass,
 }
 
@@ -25,23 +24,120 @@ impl RenderPass {
 
 pub struct RenderPassBuilder<'a> {
     context: &'a VulkanContext,
+    view_mask: u32,
+    color_format: Option<vk::Format>,
+    depth_format: Option<vk::Format>,
+    sample_count: vk::SampleCountFlags,
+    color_load_op: vk::AttachmentLoadOp,
+    color_initial_layout: vk::ImageLayout,
+    color_final_layout: vk::ImageLayout,
+    depth_store_op: vk::AttachmentStoreOp,
+    second_subpass_depth: bool,
 }
 
 impl<'a> RenderPassBuilder<'a> {
     pub fn new(context: &'a VulkanContext) -> Self {
-        RenderPassBuilder { context }
+        RenderPassBuilder {
+            context,
+            view_mask: 0,
+            color_format: None,
+            depth_format: None,
+            sample_count: vk::SampleCountFlags::TYPE_1,
+            color_load_op: vk::AttachmentLoadOp::CLEAR,
+            color_initial_layout: vk::ImageLayout::UNDEFINED,
+            color_final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+            depth_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            second_subpass_depth: true,
+        }
+    }
+
+    pub fn with_color_format(mut self, color_format: vk::Format) -> Self {
+        self.color_format = Some(color_format);
+        self
+    }
+
+    pub fn with_depth_format(mut self, depth_format: vk::Format) -> Self {
+        self.depth_format = Some(depth_format);
+        self
+    }
+
+    pub fn with_sample_count(mut self, sample_count: vk::SampleCountFlags) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// `LOAD` keeps what is already in the image and needs an initial layout other than
+    /// `UNDEFINED`, see `with_color_initial_layout`.
+    pub fn with_color_load_op(mut self, load_op: vk::AttachmentLoadOp) -> Self {
+        self.color_load_op = load_op;
+        self
+    }
+
+    pub fn with_color_initial_layout(mut self, layout: vk::ImageLayout) -> Self {
+        self.color_initial_layout = layout;
+        self
+    }
+
+    /// Layout of the rendered image once the pass ends; with multisampling this applies to the
+    /// resolve target.
+    pub fn with_color_final_layout(mut self, layout: vk::ImageLayout) -> Self {
+        self.color_final_layout = layout;
+        self
+    }
+
+    /// Use `STORE` when the depth buffer is sampled after the pass.
+    pub fn with_depth_store_op(mut self, store_op: vk::AttachmentStoreOp) -> Self {
+        self.depth_store_op = store_op;
+        self
+    }
+
+    /// Makes the second subpass color-only, e.g. for transparent or overlay geometry drawn after
+    /// the opaque pass.
+    pub fn with_second_subpass_depth(mut self, enabled: bool) -> Self {
+        self.second_subpass_depth = enabled;
+        self
+    }
+
+    /// Renders every view whose bit is set in `view_mask` in a single pass. Needs the `multiview`
+    /// feature and attachments with enough layers for the highest view.
+    pub fn with_multiview(mut self, view_mask: u32) -> Self {
+        self.view_mask = view_mask;
+        self
     }
 
     pub fn build(self) -> Result<RenderPass, VulkanError> {
+        if self.view_mask != 0 && !self.context.get_device().enabled_features().multiview {
+            return Err(VulkanError::RenderPassCreationError(String::from(
+                "Multiview render passes need the multiview feature",
+            )));
+        }
+
+        let color_format = self
+            .color_format
+            .unwrap_or_else(|| self.context.get_swapchain().get_format().format);
+        let depth_format = self
+            .depth_format
+            .unwrap_or_else(|| self.context.get_depth_resources().get_format());
+        let multisampled = self.sample_count != vk::SampleCountFlags::TYPE_1;
+
+        let (color_store_op, color_final_layout) = if multisampled {
+            (
+                vk::AttachmentStoreOp::DONT_CARE,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            )
+        } else {
+            (vk::AttachmentStoreOp::STORE, self.color_final_layout)
+        };
+
         let color_attachment = vk::AttachmentDescription::builder()
-            .format(self.context.get_swapchain().get_format().format)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::STORE)
+            .format(color_format)
+            .samples(self.sample_count)
+            .load_op(self.color_load_op)
+            .store_op(color_store_op)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+            .initial_layout(self.color_initial_layout)
+            .final_layout(color_final_layout)
             .build();
 
         let color_attachment_ref = vk::AttachmentReference::builder()
@@ -50,10 +146,10 @@ impl<'a> RenderPassBuilder<'a> {
             .build();
 
         let depth_attachment = vk::AttachmentDescription::builder()
-            .format(self.context.get_depth_resources().get_format())
-            .samples(vk::SampleCountFlags::TYPE_1)
+            .format(depth_format)
+            .samples(self.sample_count)
             .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .store_op(self.depth_store_op)
             .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
             .initial_layout(vk::ImageLayout::UNDEFINED)
@@ -65,17 +161,45 @@ impl<'a> RenderPassBuilder<'a> {
             .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
             .build();
 
+        let resolve_attachment = vk::AttachmentDescription::builder()
+            .format(color_format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(self.color_final_layout)
+            .build();
+
+        let resolve_attachment_ref = vk::AttachmentReference::builder()
+            .attachment(2)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .build();
+
+        let color_attachment_refs = [color_attachment_ref];
+        let resolve_attachment_refs = [resolve_attachment_ref];
+
+        let mut last_subpass = vk::SubpassDescription::builder()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_refs);
+
+        if self.second_subpass_depth {
+            last_subpass = last_subpass.depth_stencil_attachment(&depth_attachment_ref);
+        }
+
+        // The multisampled color is resolved into the presentable image once the last subpass ends.
+        if multisampled {
+            last_subpass = last_subpass.resolve_attachments(&resolve_attachment_refs);
+        }
+
         let subpasses = [
             vk::SubpassDescription::builder()
                 .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-                .color_attachments(&[color_attachment_ref])
-                .depth_stencil_attachment(&depth_attachment_ref)
-                .build(),
-            vk::SubpassDescription::builder()
-                .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-                .color_attachments(&[color_attachment_ref])
+                .color_attachments(&color_attachment_refs)
                 .depth_stencil_attachment(&depth_attachment_ref)
                 .build(),
+            last_subpass.build(),
         ];
 
         let dependencies = [
@@ -105,19 +229,36 @@ impl<'a> RenderPassBuilder<'a> {
                 .build(),
         ];
 
-        let render_pass_info = vk::RenderPassCreateInfo::builder()
-            .attachments(&[color_attachment, depth_attachment])
-            .subpasses(&subpasses)
-            .dependencies(&dependencies)
+        let view_masks = [self.view_mask; 2];
+        let correlation_masks = [self.view_mask];
+        let mut multiview_info = vk::RenderPassMultiviewCreateInfo::builder()
+            .view_masks(&view_masks)
+            .correlation_masks(&correlation_masks)
             .build();
 
+        let attachments = if multisampled {
+            vec![color_attachment, depth_attachment, resolve_attachment]
+        } else {
+            vec![color_attachment, depth_attachment]
+        };
+        let mut render_pass_info = vk::RenderPassCreateInfo::builder()
+            .attachments(&attachments)
+            .subpasses(&subpasses)
+            .dependencies(&dependencies);
+
+        if self.view_mask != 0 {
+            render_pass_info = render_pass_info.push_next(&mut multiview_info);
+        }
+
+        let render_pass_info = render_pass_info.build();
+
         let render_pass = self
             .context
             .get_device()
             .create_render_pass(&render_pass_info)?;
 
         Ok(RenderPass {
-            device: Rc::clone(self.context.get_device()),
+            device: Shared::clone(self.context.get_device()),
             render_pass,
         })
     }
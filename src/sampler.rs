@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use ash::vk;
+
+use crate::device::VulkanDevice;
+use crate::errors::VulkanError;
+use crate::lock::Lock;
+use crate::Shared;
+
+const MAX_ANISOTROPY: f32 = 16.0;
+
+pub struct Sampler {
+    device: Shared<VulkanDevice>,
+    sampler: vk::Sampler,
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.device.destroy_sampler(self.sampler);
+    }
+}
+
+impl Sampler {
+    pub fn get(&self) -> vk::Sampler {
+        self.sampler
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SamplerKey {
+    pub filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_mode: vk::SamplerAddressMode,
+    pub reduction_mode: Option<vk::SamplerReductionModeEXT>,
+    /// LOD clamp in whole mip levels, which keeps the key hashable.
+    pub min_lod: u32,
+    pub max_lod: u32,
+}
+
+impl Default for SamplerKey {
+    fn default() -> Self {
+        SamplerKey {
+            filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode: vk::SamplerAddressMode::REPEAT,
+            reduction_mode: None,
+            min_lod: 0,
+            max_lod: 0,
+        }
+    }
+}
+
+pub struct SamplerCache {
+    device: Shared<VulkanDevice>,
+    max_anisotropy: f32,
+    samplers: Lock<HashMap<SamplerKey, Shared<Sampler>>>,
+}
+
+impl SamplerCache {
+    pub(crate) fn new(device: Shared<VulkanDevice>, max_sampler_anisotropy: f32) -> Self {
+        SamplerCache {
+            device,
+            max_anisotropy: max_sampler_anisotropy.min(MAX_ANISOTROPY),
+            samplers: Lock::new(HashMap::new()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samplers.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.lock().is_empty()
+    }
+
+    pub fn get_or_create(&self, key: SamplerKey) -> Result<Shared<Sampler>, VulkanError> {
+        let mut samplers = self.samplers.lock();
+        if let Some(sampler) = samplers.get(&key) {
+            return Ok(Shared::clone(sampler));
+        }
+
+        let sampler = Shared::new(Sampler {
+            device: Shared::clone(&self.device),
+            sampler: self.create_sampler(&key)?,
+        });
+        samplers.insert(key, Shared::clone(&sampler));
+
+        Ok(sampler)
+    }
+
+    fn create_sampler(&self, key: &SamplerKey) -> Result<vk::Sampler, VulkanError> {
+        let anisotropy_enable = self.device.enabled_features().sampler_anisotropy;
+
+        let mut reduction_mode_info = vk::SamplerReductionModeCreateInfoEXT::builder()
+            .reduction_mode(
+                key.reduction_mode
+                    .unwrap_or(vk::SamplerReductionModeEXT::WEIGHTED_AVERAGE),
+            )
+            .build();
+
+        let mut sampler_info = vk::SamplerCreateInfo::builder()
+            .mag_filter(key.filter)
+            .min_filter(key.filter)
+            .address_mode_u(key.address_mode)
+            .address_mode_v(key.address_mode)
+            .address_mode_w(key.address_mode)
+            .anisotropy_enable(anisotropy_enable)
+            .max_anisotropy(if anisotropy_enable {
+                self.max_anisotropy
+            } else {
+                1.0
+            })
+            .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
+            .unnormalized_coordinates(false)
+            .compare_enable(false)
+            .compare_op(vk::CompareOp::ALWAYS)
+            .mipmap_mode(key.mipmap_mode)
+            .min_lod(key.min_lod as f32)
+            .max_lod(key.max_lod as f32);
+
+        if key.reduction_mode.is_some() {
+            sampler_info = sampler_info.push_next(&mut reduction_mode_info);
+        }
+
+        let sampler_info = sampler_info.build();
+
+        self.device.create_sampler(&sampler_info)
+    }
+}
@@ -0,0 +1,117 @@
+use ash::vk;
+
+use crate::device::VulkanDevice;
+use crate::errors::VulkanError;
+use crate::vulkan_context::VulkanContext;
+use crate::Shared;
+
+/// Secondary level command buffers with a command pool of their own, so that each recording
+/// thread can own one set. They are recorded inside a render pass and replayed from the
+/// primary command buffer with `VulkanContext::execute_secondary_command_buffers`.
+pub struct SecondaryCommandBuffers {
+    device: Shared<VulkanDevice>,
+    command_pool: vk::CommandPool,
+    command_buffers: Vec<vk::CommandBuffer>,
+}
+
+impl Drop for SecondaryCommandBuffers {
+    fn drop(&mut self) {
+        self.device
+            .free_command_buffers(self.command_pool, &self.command_buffers);
+        self.device.destroy_command_pool(self.command_pool);
+    }
+}
+
+impl SecondaryCommandBuffers {
+    pub fn get(&self, index: usize) -> vk::CommandBuffer {
+        self.command_buffers[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.command_buffers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.command_buffers.is_empty()
+    }
+
+    pub fn begin(
+        &self,
+        index: usize,
+        render_pass: vk::RenderPass,
+        subpass: u32,
+        frame_buffer: vk::Framebuffer,
+    ) -> Result<vk::CommandBuffer, VulkanError> {
+        let command_buffer = self.command_buffers[index];
+
+        let inheritance_info = vk::CommandBufferInheritanceInfo::builder()
+            .render_pass(render_pass)
+            .subpass(subpass)
+            .framebuffer(frame_buffer)
+            .build();
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(
+                vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE
+                    | vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+            )
+            .inheritance_info(&inheritance_info)
+            .build();
+        self.device
+            .begin_command_buffer(command_buffer, &begin_info)?;
+
+        Ok(command_buffer)
+    }
+
+    pub fn end(&self, index: usize) -> Result<(), VulkanError> {
+        self.device.end_command_buffer(self.command_buffers[index])
+    }
+}
+
+pub struct SecondaryCommandBuffersBuilder<'a> {
+    context: &'a VulkanContext,
+    count: u32,
+}
+
+impl<'a> SecondaryCommandBuffersBuilder<'a> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        SecondaryCommandBuffersBuilder { context, count: 1 }
+    }
+
+    pub fn with_count(mut self, count: u32) -> Self {
+        self.count = count;
+        self
+    }
+
+    pub fn build(self) -> Result<SecondaryCommandBuffers, VulkanError> {
+        let device = self.context.get_device();
+
+        let pool_info = vk::CommandPoolCreateInfo::builder()
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER)
+            .queue_family_index(
+                self.context
+                    .get_physical_device()
+                    .get_graphics_queue_family(),
+            )
+            .build();
+        let command_pool = device.create_command_pool(&pool_info)?;
+
+        let alloc_info = vk::CommandBufferAllocateInfo::builder()
+            .level(vk::CommandBufferLevel::SECONDARY)
+            .command_pool(command_pool)
+            .command_buffer_count(self.count)
+            .build();
+        let command_buffers = match device.allocate_command_buffers(&alloc_info) {
+            Ok(command_buffers) => command_buffers,
+            Err(err) => {
+                device.destroy_command_pool(command_pool);
+                return Err(err);
+            }
+        };
+
+        Ok(SecondaryCommandBuffers {
+            device: Shared::clone(device),
+            command_pool,
+            command_buffers,
+        })
+    }
+}
@@ -1,16 +1,19 @@
-use std::fs::File;
-use std::path::Path;
-use std::rc::Rc;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use ash::util::read_spv;
 use ash::vk;
 
 use crate::device::VulkanDevice;
 use crate::errors::VulkanError;
+use crate::Shared;
 
 pub struct ShaderModule {
-    device: Rc<VulkanDevice>,
+    device: Shared<VulkanDevice>,
     shader_module: vk::ShaderModule,
+    path: PathBuf,
+    modified: Option<SystemTime>,
 }
 
 impl Drop for ShaderModule {
@@ -23,15 +26,51 @@ impl ShaderModule {
     pub fn get(&self) -> vk::ShaderModule {
         self.shader_module
     }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_modified(&self) -> bool {
+        let modified = modified_time(&self.path);
+        modified.is_some() && modified != self.modified
+    }
+
+    pub fn reload(&mut self) -> Result<(), VulkanError> {
+        let modified = modified_time(&self.path);
+        let shader_module = load_shader_module(&self.device, &self.path)?;
+
+        self.device.destroy_shader_module(self.shader_module);
+        self.shader_module = shader_module;
+        self.modified = modified;
+
+        Ok(())
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn load_shader_module(device: &VulkanDevice, path: &Path) -> Result<vk::ShaderModule, VulkanError> {
+    let mut file =
+        File::open(path).map_err(|err| VulkanError::ShaderCreationError(err.to_string()))?;
+    let shader =
+        read_spv(&mut file).map_err(|err| VulkanError::ShaderCreationError(err.to_string()))?;
+
+    let create_info = vk::ShaderModuleCreateInfo::builder().code(&shader).build();
+    device.create_shader_module(&create_info)
 }
 
 pub struct ShaderModuleBuilder<'a> {
-    device: Rc<VulkanDevice>,
+    device: Shared<VulkanDevice>,
     path: Option<&'a Path>,
 }
 
 impl<'a> ShaderModuleBuilder<'a> {
-    pub fn new(device: Rc<VulkanDevice>) -> Self {
+    pub fn new(device: Shared<VulkanDevice>) -> Self {
         ShaderModuleBuilder { device, path: None }
     }
 
@@ -46,17 +85,14 @@ impl<'a> ShaderModuleBuilder<'a> {
             .ok_or(VulkanError::ShaderCreationError(String::from(
                 "Path to the shader not provided",
             )))?;
-        let mut file = File::open(shader_path)
-            .map_err(|err| VulkanError::ShaderCreationError(err.to_string()))?;
-        let shader =
-            read_spv(&mut file).map_err(|err| VulkanError::ShaderCreationError(err.to_string()))?;
-
-        let create_info = vk::ShaderModuleCreateInfo::builder().code(&shader).build();
-        let shader_module = self.device.create_shader_module(&create_info)?;
+        let modified = modified_time(shader_path);
+        let shader_module = load_shader_module(&self.device, shader_path)?;
 
         Ok(ShaderModule {
             device: self.device,
             shader_module,
+            path: shader_path.to_path_buf(),
+            modified,
         })
     }
 }
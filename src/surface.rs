@@ -3,7 +3,7 @@ use ash::vk;
 
 use crate::errors::VulkanError;
 use crate::instance::VulkanInstance;
-use crate::windows::Win32Window;
+use crate::windows::PlatformWindow;
 
 pub struct SwapchainSupportDetails {
     pub capabilities: vk::SurfaceCapabilitiesKHR,
@@ -63,7 +63,7 @@ impl Surface {
             self.surface_loader
                 .get_physical_device_surface_capabilities(device, self.surface)
         }
-        .map_err(|err| VulkanError::SurfaceError(err.to_string()))
+        .map_err(VulkanError::from)
     }
 
     pub fn get_physical_device_surface_formats(
@@ -74,7 +74,7 @@ impl Surface {
             self.surface_loader
                 .get_physical_device_surface_formats(device, self.surface)
         }
-        .map_err(|err| VulkanError::SurfaceError(err.to_string()))
+        .map_err(VulkanError::from)
     }
 
     pub fn get_physical_device_surface_present_modes(
@@ -85,32 +85,47 @@ impl Surface {
             self.surface_loader
                 .get_physical_device_surface_present_modes(device, self.surface)
         }
-        .map_err(|err| VulkanError::SurfaceError(err.to_string()))
+        .map_err(VulkanError::from)
     }
 }
 
 pub struct SurfaceBuilder<'a> {
     instance: &'a VulkanInstance,
-    window: Win32Window,
+    window: Option<PlatformWindow>,
 }
 
 impl<'a> SurfaceBuilder<'a> {
     pub fn new(instance: &'a VulkanInstance) -> Self {
         SurfaceBuilder {
             instance,
-            window: Win32Window::default(),
+            window: None,
         }
     }
 
-    pub fn with_window(mut self, window: Win32Window) -> Self {
-        self.window = window;
+    pub fn with_window(mut self, window: PlatformWindow) -> Self {
+        self.window = Some(window);
         self
     }
 
     pub fn build(self) -> Result<Surface, VulkanError> {
-        let (surface_loader, surface) = self
-            .instance
-            .create_win_32_surface(self.window.hinstance, self.window.hwnd)?;
+        let window = self.window.ok_or_else(|| {
+            VulkanError::SurfaceError(String::from("Window not provided or not supported"))
+        })?;
+
+        let (surface_loader, surface) = match window {
+            #[cfg(windows)]
+            PlatformWindow::Win32(window) => self
+                .instance
+                .create_win_32_surface(window.hinstance, window.hwnd)?,
+            #[cfg(unix)]
+            PlatformWindow::Xlib(window) => self
+                .instance
+                .create_xlib_surface(window.display, window.window)?,
+            #[cfg(unix)]
+            PlatformWindow::Wayland(window) => self
+                .instance
+                .create_wayland_surface(window.display, window.surface)?,
+        };
 
         Ok(Surface {
             surface_loader,
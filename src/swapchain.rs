@@ -1,15 +1,20 @@
-use std::rc::Rc;
-
 use ash::extensions::khr;
 use ash::vk;
 
 use crate::device::VulkanDevice;
 use crate::errors::VulkanError;
 use crate::vulkan_context::VulkanContext;
-use std::mem;
+use crate::Shared;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SwapchainStatus {
+    Optimal,
+    Suboptimal,
+    OutOfDate,
+}
 
 pub struct Swapchain {
-    device: Rc<VulkanDevice>,
+    device: Shared<VulkanDevice>,
     swapchain_loader: Option<khr::Swapchain>,
     swapchain: vk::SwapchainKHR,
     swapchain_format: vk::SurfaceFormatKHR,
@@ -45,6 +50,18 @@ impl Swapchain {
         self.image_views[index]
     }
 
+    pub fn get_images(&self) -> &[vk::Image] {
+        &self.swapchain_images
+    }
+
+    pub fn get_image_views(&self) -> &[vk::ImageView] {
+        &self.image_views
+    }
+
+    pub fn get_image_count(&self) -> usize {
+        self.swapchain_images.len()
+    }
+
     pub fn get_format(&self) -> vk::SurfaceFormatKHR {
         self.swapchain_format
     }
@@ -53,47 +70,74 @@ impl Swapchain {
         self.swapchain_extent
     }
 
-    pub fn acquire_next_image(&self, semaphore: vk::Semaphore) -> Result<usize, VulkanError> {
-        let (index, _) = unsafe {
+    pub fn acquire_next_image(
+        &self,
+        semaphore: vk::Semaphore,
+    ) -> Result<(usize, SwapchainStatus), VulkanError> {
+        match unsafe {
             self.swapchain_loader.as_ref().unwrap().acquire_next_image(
                 self.swapchain,
-                std::u64::MAX,
+                u64::MAX,
                 semaphore,
                 vk::Fence::null(),
             )
+        } {
+            Ok((index, false)) => Ok((index as usize, SwapchainStatus::Optimal)),
+            Ok((index, true)) => Ok((index as usize, SwapchainStatus::Suboptimal)),
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok((0, SwapchainStatus::OutOfDate)),
+            Err(err) => Err(VulkanError::from(err)),
         }
-        .map_err(|err| VulkanError::SwapchainError(err.to_string()))?;
-        Ok(index as usize)
     }
 
     pub fn queue_present(
         &self,
         semaphore: vk::Semaphore,
         image_index: u32,
-    ) -> Result<(), VulkanError> {
+    ) -> Result<SwapchainStatus, VulkanError> {
+        Swapchain::present_status(self.queue_present_result(semaphore, image_index))
+    }
+
+    pub fn present_status(result: vk::Result) -> Result<SwapchainStatus, VulkanError> {
+        match result {
+            vk::Result::SUCCESS => Ok(SwapchainStatus::Optimal),
+            vk::Result::SUBOPTIMAL_KHR => Ok(SwapchainStatus::Suboptimal),
+            vk::Result::ERROR_OUT_OF_DATE_KHR => Ok(SwapchainStatus::OutOfDate),
+            err => Err(VulkanError::from(err)),
+        }
+    }
+
+    pub fn queue_present_result(&self, semaphore: vk::Semaphore, image_index: u32) -> vk::Result {
+        let wait_semaphores = [semaphore];
+        let swapchains = [self.swapchain];
+        let image_indices = [image_index];
         let info = vk::PresentInfoKHR::builder()
-            .wait_semaphores(&[semaphore])
-            .swapchains(&[self.swapchain])
-            .image_indices(&[image_index])
+            .wait_semaphores(&wait_semaphores)
+            .swapchains(&swapchains)
+            .image_indices(&image_indices)
             .build();
-        unsafe {
+        match unsafe {
             self.swapchain_loader
                 .as_ref()
                 .unwrap()
-                .queue_present(self.device.get_queue(), &info)
+                .queue_present(self.device.get_present_queue(), &info)
+        } {
+            Ok(false) => vk::Result::SUCCESS,
+            Ok(true) => vk::Result::SUBOPTIMAL_KHR,
+            Err(err) => err,
         }
-        .map_err(|err| VulkanError::SwapchainError(err.to_string()))?;
-
-        Ok(())
     }
 }
 
 pub struct SwapchainBuilder<'a> {
     context: &'a VulkanContext,
-    old_swapchain: Option<Swapchain>,
+    old_swapchain: Option<&'a Swapchain>,
     frames_count: u32,
     width: u32,
     height: u32,
+    present_mode: Option<vk::PresentModeKHR>,
+    desired_format: Option<vk::SurfaceFormatKHR>,
+    image_usage: vk::ImageUsageFlags,
+    image_array_layers: u32,
 }
 
 impl<'a> SwapchainBuilder<'a> {
@@ -104,10 +148,15 @@ impl<'a> SwapchainBuilder<'a> {
             frames_count: 1,
             width: 0,
             height: 0,
+            present_mode: None,
+            desired_format: None,
+            image_usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::STORAGE,
+            image_array_layers: 1,
         }
     }
 
-    pub fn with_old_swapchain(mut self, old_swapchain: Option<Swapchain>) -> Self {
+    /// The old swapchain is retired by `build` and can be dropped once the new one replaces it.
+    pub fn with_old_swapchain(mut self, old_swapchain: Option<&'a Swapchain>) -> Self {
         self.old_swapchain = old_swapchain;
         self
     }
@@ -127,37 +176,75 @@ impl<'a> SwapchainBuilder<'a> {
         self
     }
 
-    pub fn build(mut self) -> Result<Swapchain, VulkanError> {
+    pub fn with_present_mode(mut self, present_mode: vk::PresentModeKHR) -> Self {
+        self.present_mode = Some(present_mode);
+        self
+    }
+
+    /// Requests a specific surface format and color space. When the surface does
+    /// not support that exact pair, the builder falls back to `B8G8R8A8_UNORM` with
+    /// `SRGB_NONLINEAR`, and then to the first format reported by the surface.
+    pub fn with_desired_format(mut self, desired_format: vk::SurfaceFormatKHR) -> Self {
+        self.desired_format = Some(desired_format);
+        self
+    }
+
+    pub fn with_image_usage(mut self, image_usage: vk::ImageUsageFlags) -> Self {
+        self.image_usage = image_usage;
+        self
+    }
+
+    /// More than one layer is meant for stereo presentation; the image views then become 2D
+    /// arrays, to be rendered with multiview.
+    pub fn with_image_array_layers(mut self, image_array_layers: u32) -> Self {
+        self.image_array_layers = image_array_layers;
+        self
+    }
+
+    pub fn build(self) -> Result<Swapchain, VulkanError> {
+        self.check_image_array_layers()?;
+
         let swapchain_format = self.choose_surface_format()?;
         let present_mode = self.choose_present_mode()?;
         let swapchain_extent = self.choose_surface_extent()?;
-
-        let old_swapchain = if self.old_swapchain.is_some() {
-            self.old_swapchain.as_ref().unwrap().get()
-        } else {
-            vk::SwapchainKHR::null()
-        };
+        let image_count = self.choose_image_count()?;
+
+        let old_swapchain = self
+            .old_swapchain
+            .map_or_else(vk::SwapchainKHR::null, Swapchain::get);
+
+        let physical_device = self.context.get_physical_device();
+        let queue_family_indices = [
+            physical_device.get_graphics_queue_family(),
+            physical_device.get_present_queue_family(),
+        ];
+        let (sharing_mode, queue_family_indices) =
+            if queue_family_indices[0] != queue_family_indices[1] {
+                (vk::SharingMode::CONCURRENT, &queue_family_indices[..])
+            } else {
+                (vk::SharingMode::EXCLUSIVE, &[][..])
+            };
 
         let info = vk::SwapchainCreateInfoKHR::builder()
             .surface(self.context.get_surface().get())
-            .min_image_count(self.frames_count)
+            .min_image_count(image_count)
             .image_format(swapchain_format.format)
             .image_color_space(swapchain_format.color_space)
             .image_extent(swapchain_extent)
-            .image_array_layers(1)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::STORAGE)
+            .image_array_layers(self.image_array_layers)
+            .image_usage(self.image_usage)
             .pre_transform(vk::SurfaceTransformFlagsKHR::IDENTITY)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
             .present_mode(present_mode)
             .clipped(true)
-            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .image_sharing_mode(sharing_mode)
+            .queue_family_indices(queue_family_indices)
             .old_swapchain(old_swapchain)
             .build();
 
-        let swapchain_loader = if self.old_swapchain.is_some() {
-            self.old_swapchain.as_mut().unwrap().swapchain_loader.take()
-        } else {
-            Some(self.context.get_device().new_swapchain())
+        let swapchain_loader = match self.old_swapchain {
+            Some(old_swapchain) => old_swapchain.swapchain_loader.clone(),
+            None => Some(self.context.get_device().new_swapchain()),
         };
 
         let swapchain = unsafe {
@@ -166,11 +253,7 @@ impl<'a> SwapchainBuilder<'a> {
                 .unwrap()
                 .create_swapchain(&info, None)
         }
-        .map_err(|err| VulkanError::SwapchainCreationError(err.to_string()))?;
-
-        if let Some(old_swapchain) = self.old_swapchain.take() {
-            mem::drop(old_swapchain);
-        }
+        .map_err(VulkanError::from)?;
 
         let swapchain_images = unsafe {
             swapchain_loader
@@ -178,14 +261,20 @@ impl<'a> SwapchainBuilder<'a> {
                 .unwrap()
                 .get_swapchain_images(swapchain)
         }
-        .map_err(|err| VulkanError::SwapchainCreationError(err.to_string()))?;
+        .map_err(VulkanError::from)?;
+
+        let view_type = if self.image_array_layers > 1 {
+            vk::ImageViewType::TYPE_2D_ARRAY
+        } else {
+            vk::ImageViewType::TYPE_2D
+        };
 
         let image_views = swapchain_images
             .iter()
             .map(|image| {
                 let view_info = vk::ImageViewCreateInfo::builder()
                     .image(*image)
-                    .view_type(vk::ImageViewType::TYPE_2D)
+                    .view_type(view_type)
                     .format(swapchain_format.format)
                     .components(
                         vk::ComponentMapping::builder()
@@ -201,7 +290,7 @@ impl<'a> SwapchainBuilder<'a> {
                             .base_mip_level(0)
                             .level_count(1)
                             .base_array_layer(0)
-                            .layer_count(1)
+                            .layer_count(self.image_array_layers)
                             .build(),
                     )
                     .build();
@@ -214,7 +303,7 @@ impl<'a> SwapchainBuilder<'a> {
             .collect();
 
         Ok(Swapchain {
-            device: Rc::clone(self.context.get_device()),
+            device: Shared::clone(self.context.get_device()),
             swapchain_loader,
             swapchain,
             swapchain_format,
@@ -230,27 +319,26 @@ impl<'a> SwapchainBuilder<'a> {
             .get_surface()
             .get_physical_device_surface_formats(self.context.get_physical_device().get())?;
 
-        Ok(
-            if formats.len() == 1 && formats[0].format == vk::Format::UNDEFINED {
-                vk::SurfaceFormatKHR::builder()
-                    .format(vk::Format::B8G8R8A8_UNORM)
-                    .color_space(vk::ColorSpaceKHR::SRGB_NONLINEAR)
-                    .build()
-            } else {
-                formats
-                    .iter()
-                    .find_map(|format| {
-                        if format.format == vk::Format::B8G8R8A8_UNORM
-                            && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
-                        {
-                            Some(*format)
-                        } else {
-                            None
-                        }
-                    })
-                    .unwrap_or(formats[0])
-            },
-        )
+        let default_format = vk::SurfaceFormatKHR::builder()
+            .format(vk::Format::B8G8R8A8_UNORM)
+            .color_space(vk::ColorSpaceKHR::SRGB_NONLINEAR)
+            .build();
+
+        if formats.len() == 1 && formats[0].format == vk::Format::UNDEFINED {
+            return Ok(self.desired_format.unwrap_or(default_format));
+        }
+
+        let is_supported = |wanted: &vk::SurfaceFormatKHR| {
+            formats.iter().any(|format| {
+                format.format == wanted.format && format.color_space == wanted.color_space
+            })
+        };
+
+        Ok(self
+            .desired_format
+            .filter(is_supported)
+            .or_else(|| Some(default_format).filter(is_supported))
+            .unwrap_or(formats[0]))
     }
 
     fn choose_present_mode(&self) -> Result<vk::PresentModeKHR, VulkanError> {
@@ -259,12 +347,44 @@ impl<'a> SwapchainBuilder<'a> {
             .get_surface()
             .get_physical_device_surface_present_modes(self.context.get_physical_device().get())?;
 
+        let preferred_mode = self.present_mode.unwrap_or(vk::PresentModeKHR::MAILBOX);
+
         Ok(present_modes
             .into_iter()
-            .find(|mode| *mode == vk::PresentModeKHR::MAILBOX)
+            .find(|mode| *mode == preferred_mode)
             .unwrap_or(vk::PresentModeKHR::FIFO))
     }
 
+    fn check_image_array_layers(&self) -> Result<(), VulkanError> {
+        let caps = self
+            .context
+            .get_surface()
+            .get_physical_device_surface_capabilities(self.context.get_physical_device().get())?;
+
+        if self.image_array_layers == 0 || self.image_array_layers > caps.max_image_array_layers {
+            return Err(VulkanError::SwapchainCreationError(format!(
+                "{} image array layers requested, the surface supports 1 to {}",
+                self.image_array_layers, caps.max_image_array_layers
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn choose_image_count(&self) -> Result<u32, VulkanError> {
+        let caps = self
+            .context
+            .get_surface()
+            .get_physical_device_surface_capabilities(self.context.get_physical_device().get())?;
+
+        let image_count = self.frames_count.max(caps.min_image_count);
+        Ok(if caps.max_image_count > 0 {
+            image_count.min(caps.max_image_count)
+        } else {
+            image_count
+        })
+    }
+
     fn choose_surface_extent(&self) -> Result<vk::Extent2D, VulkanError> {
         let caps = self
             .context
@@ -1,28 +1,37 @@
+use std::mem;
 use std::os::raw::c_void;
-use std::rc::Rc;
+#[cfg(feature = "image")]
+use std::path::Path;
+use std::slice;
 
 use ash::vk;
 
 use crate::buffer::{BufferBuilder, BufferType};
 use crate::device::VulkanDevice;
 use crate::errors::VulkanError;
+use crate::extensions::DeviceExtensions;
 use crate::image;
+use crate::memory_allocator::Allocation;
+use crate::sampler::{Sampler, SamplerKey};
 use crate::vulkan_context::VulkanContext;
+use crate::Shared;
 
 pub struct Texture {
-    device: Rc<VulkanDevice>,
+    device: Shared<VulkanDevice>,
     texture_image: vk::Image,
-    texture_image_memory: vk::DeviceMemory,
+    texture_image_memory: Allocation,
     texture_image_view: vk::ImageView,
-    texture_sampler: vk::Sampler,
+    texture_sampler: Shared<Sampler>,
+    descriptor_image_info: vk::DescriptorImageInfo,
+    extent: vk::Extent2D,
+    mip_levels: u32,
+    view_type: vk::ImageViewType,
 }
 
 impl Drop for Texture {
     fn drop(&mut self) {
-        self.device.destroy_sampler(self.texture_sampler);
         self.device.destroy_image_view(self.texture_image_view);
         self.device.destroy_image(self.texture_image);
-        self.device.free_memory(self.texture_image_memory);
     }
 }
 
@@ -32,7 +41,133 @@ impl Texture {
     }
 
     pub fn get_sampler(&self) -> vk::Sampler {
-        self.texture_sampler
+        self.texture_sampler.get()
+    }
+
+    pub fn get_mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    /// Fills one mip level, e.g. once it has been streamed in. The level is not sampled until
+    /// it is part of the view range, see `set_mip_range`.
+    pub fn upload_mip_level(
+        &self,
+        context: &VulkanContext,
+        mip_level: u32,
+        pixels: &[u8],
+    ) -> Result<(), VulkanError> {
+        if self.view_type != vk::ImageViewType::TYPE_2D {
+            return Err(VulkanError::TextureCreationError(String::from(
+                "Only 2D textures support mip uploads",
+            )));
+        }
+        if mip_level >= self.mip_levels {
+            return Err(VulkanError::TextureCreationError(format!(
+                "Mip level {} is out of the {} levels of the texture",
+                mip_level, self.mip_levels
+            )));
+        }
+
+        let range = image::TransitionRange {
+            base_mip_level: mip_level,
+            level_count: 1,
+            ..Default::default()
+        };
+        image::transition_image_layout_range(
+            context,
+            self.texture_image,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            range,
+        )?;
+
+        upload_mip_level(
+            context,
+            self.texture_image,
+            (self.extent.width >> mip_level).max(1),
+            (self.extent.height >> mip_level).max(1),
+            mip_level,
+            pixels,
+        )?;
+
+        image::transition_image_layout_range(
+            context,
+            self.texture_image,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            range,
+        )
+    }
+
+    /// Recreates the view so that only `level_count` levels from `base_mip_level` are sampled,
+    /// which is how streamed textures reveal their resident mips. The previous view is destroyed
+    /// right away: wait for the frames using it, and write the descriptors again afterwards.
+    pub fn set_mip_range(
+        &mut self,
+        context: &VulkanContext,
+        base_mip_level: u32,
+        level_count: u32,
+    ) -> Result<(), VulkanError> {
+        if self.view_type != vk::ImageViewType::TYPE_2D {
+            return Err(VulkanError::TextureCreationError(String::from(
+                "Only 2D textures support mip ranges",
+            )));
+        }
+        if level_count == 0 || base_mip_level + level_count > self.mip_levels {
+            return Err(VulkanError::TextureCreationError(format!(
+                "Mip range {}..{} is out of the {} levels of the texture",
+                base_mip_level,
+                base_mip_level + level_count,
+                self.mip_levels
+            )));
+        }
+
+        let image_view = image::create_image_view_mip_range(
+            context,
+            self.texture_image,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageAspectFlags::COLOR,
+            base_mip_level,
+            level_count,
+        )?;
+        self.device.destroy_image_view(self.texture_image_view);
+        self.texture_image_view = image_view;
+        self.descriptor_image_info.image_view = image_view;
+
+        Ok(())
+    }
+
+    /// Combined image sampler write for `binding`; the image info it points to is owned by the
+    /// texture, so the write cannot outlive it.
+    pub fn write_descriptor(
+        &self,
+        descriptor_set: vk::DescriptorSet,
+        binding: u32,
+    ) -> vk::WriteDescriptorSetBuilder<'_> {
+        vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(binding)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(slice::from_ref(&self.descriptor_image_info))
+    }
+
+    /// Hands the image, its view and its memory over to the caller, who becomes responsible
+    /// for destroying and freeing them. The sampler stays owned by the sampler cache.
+    pub fn into_raw(mut self) -> Result<(vk::Image, vk::ImageView, vk::DeviceMemory), VulkanError> {
+        if self.texture_image_memory.is_suballocated() {
+            return Err(VulkanError::TextureCreationError(String::from(
+                "Cannot release a suballocated texture",
+            )));
+        }
+
+        let memory = self.texture_image_memory.release();
+        let image = mem::replace(&mut self.texture_image, vk::Image::null());
+        let image_view = mem::replace(&mut self.texture_image_view, vk::ImageView::null());
+
+        Ok((image, image_view, memory))
     }
 }
 
@@ -41,6 +176,8 @@ pub struct TextureBuilder<'a> {
     width: u32,
     height: u32,
     pixels: Vec<u8>,
+    reduction_mode: Option<vk::SamplerReductionModeEXT>,
+    mip_levels: u32,
 }
 
 impl<'a> TextureBuilder<'a> {
@@ -50,9 +187,23 @@ impl<'a> TextureBuilder<'a> {
             width: 0,
             height: 0,
             pixels: vec![],
+            reduction_mode: None,
+            mip_levels: 1,
         }
     }
 
+    #[cfg(feature = "image")]
+    pub fn from_path(context: &'a VulkanContext, path: &Path) -> Result<Self, VulkanError> {
+        let decoded = ::image::open(path)
+            .map_err(|err| VulkanError::TextureCreationError(err.to_string()))?
+            .into_rgba8();
+
+        Ok(TextureBuilder::new(context)
+            .with_width(decoded.width())
+            .with_height(decoded.height())
+            .with_pixels(decoded.as_raw()))
+    }
+
     pub fn with_width(mut self, width: u32) -> Self {
         self.width = width;
         self
@@ -68,117 +219,322 @@ impl<'a> TextureBuilder<'a> {
         self
     }
 
+    pub fn with_reduction_mode(mut self, reduction_mode: vk::SamplerReductionModeEXT) -> Self {
+        self.reduction_mode = Some(reduction_mode);
+        self
+    }
+
+    /// Allocates the levels below the full resolution one, to be filled with
+    /// `Texture::upload_mip_level`. Without pixels, level 0 is left to be uploaded as well.
+    pub fn with_mip_levels(mut self, mip_levels: u32) -> Self {
+        self.mip_levels = mip_levels;
+        self
+    }
+
     pub fn build(self) -> Result<Texture, VulkanError> {
-        let image_size = (self.width * self.height * 4) as vk::DeviceSize;
-        let data = self.pixels.as_ptr() as *const c_void;
+        if self.reduction_mode.is_some()
+            && !self
+                .context
+                .get_device()
+                .is_extension_enabled(DeviceExtensions::ExtSamplerFilterMinmax)
+        {
+            return Err(VulkanError::TextureCreationError(String::from(
+                "Sampler reduction mode requires VK_EXT_sampler_filter_minmax",
+            )));
+        }
 
-        let staging_buffer = BufferBuilder::new(self.context)
-            .with_type(BufferType::Staging)
-            .with_size(image_size)
-            .build()?;
+        if self.mip_levels == 0 {
+            return Err(VulkanError::TextureCreationError(String::from(
+                "A texture needs at least one mip level",
+            )));
+        }
 
-        staging_buffer.copy_data(data)?;
+        let (texture_image, texture_image_memory) = image::ImageBuilder::new(self.context)
+            .with_width(self.width)
+            .with_height(self.height)
+            .with_format(vk::Format::R8G8B8A8_UNORM)
+            .with_usage(vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED)
+            .with_mip_levels(self.mip_levels)
+            .build()?;
 
-        let (texture_image, texture_image_memory) = image::create_image(
-            self.context,
-            self.width,
-            self.height,
-            vk::Format::R8G8B8A8_UNORM,
-            vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-        )?;
+        let range = image::TransitionRange {
+            level_count: self.mip_levels,
+            ..Default::default()
+        };
 
-        image::transition_image_layout(
+        image::transition_image_layout_range(
             self.context,
             texture_image,
             vk::Format::R8G8B8A8_UNORM,
             vk::ImageLayout::UNDEFINED,
             vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            range,
         )?;
 
-        self.copy_buffer_to_image(staging_buffer.get(), texture_image, self.width, self.height)?;
+        if !self.pixels.is_empty() || self.mip_levels == 1 {
+            upload_mip_level(
+                self.context,
+                texture_image,
+                self.width,
+                self.height,
+                0,
+                &self.pixels,
+            )?;
+        }
 
-        image::transition_image_layout(
+        image::transition_image_layout_range(
             self.context,
             texture_image,
             vk::Format::R8G8B8A8_UNORM,
             vk::ImageLayout::TRANSFER_DST_OPTIMAL,
             vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            range,
         )?;
 
-        let texture_image_view = image::create_image_view(
+        let texture_image_view = image::create_image_view_mip_range(
             self.context,
             texture_image,
             vk::Format::R8G8B8A8_UNORM,
             vk::ImageAspectFlags::COLOR,
+            0,
+            self.mip_levels,
         )?;
 
-        let sampler_info = vk::SamplerCreateInfo::builder()
-            .mag_filter(vk::Filter::LINEAR)
-            .min_filter(vk::Filter::LINEAR)
-            .address_mode_u(vk::SamplerAddressMode::REPEAT)
-            .address_mode_v(vk::SamplerAddressMode::REPEAT)
-            .address_mode_w(vk::SamplerAddressMode::REPEAT)
-            .anisotropy_enable(true)
-            .max_anisotropy(16.0)
-            .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
-            .unnormalized_coordinates(false)
-            .compare_enable(false)
-            .compare_op(vk::CompareOp::ALWAYS)
-            .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
-            .build();
-
-        let texture_sampler = self.context.get_device().create_sampler(&sampler_info)?;
+        let texture_sampler = self.context.get_sampler_cache().get_or_create(SamplerKey {
+            reduction_mode: self.reduction_mode,
+            max_lod: self.mip_levels - 1,
+            ..Default::default()
+        })?;
+
+        let descriptor_image_info = descriptor_image_info(texture_image_view, &texture_sampler);
 
         Ok(Texture {
-            device: Rc::clone(&self.context.get_device()),
+            device: Shared::clone(&self.context.get_device()),
             texture_image,
             texture_image_memory,
             texture_image_view,
             texture_sampler,
+            descriptor_image_info,
+            extent: vk::Extent2D {
+                width: self.width,
+                height: self.height,
+            },
+            mip_levels: self.mip_levels,
+            view_type: vk::ImageViewType::TYPE_2D,
         })
     }
+}
 
-    fn copy_buffer_to_image(
-        &self,
-        buffer: vk::Buffer,
-        image: vk::Image,
-        width: u32,
-        height: u32,
-    ) -> Result<(), VulkanError> {
-        let command_buffer = self.context.begin_single_time_commands()?;
+pub struct CubemapBuilder<'a> {
+    context: &'a VulkanContext,
+    size: u32,
+    faces: Vec<Vec<u8>>,
+}
+
+impl<'a> CubemapBuilder<'a> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        CubemapBuilder {
+            context,
+            size: 0,
+            faces: vec![],
+        }
+    }
+
+    #[cfg(feature = "image")]
+    pub fn from_paths(context: &'a VulkanContext, paths: &[&Path; 6]) -> Result<Self, VulkanError> {
+        let mut builder = CubemapBuilder::new(context);
+        for path in paths.iter() {
+            let decoded = ::image::open(path)
+                .map_err(|err| VulkanError::TextureCreationError(err.to_string()))?
+                .into_rgba8();
+            if decoded.width() != decoded.height() {
+                return Err(VulkanError::TextureCreationError(format!(
+                    "Cubemap face {} is not square",
+                    path.display()
+                )));
+            }
+            builder.size = decoded.width();
+            builder.faces.push(decoded.into_raw());
+        }
+        Ok(builder)
+    }
+
+    pub fn with_size(mut self, size: u32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_faces(mut self, faces: &[&[u8]; 6]) -> Self {
+        self.faces = faces.iter().map(|face| face.to_vec()).collect();
+        self
+    }
+
+    pub fn build(self) -> Result<Texture, VulkanError> {
+        let face_size = (self.size * self.size * 4) as usize;
+        if self.faces.len() != 6 || self.faces.iter().any(|face| face.len() != face_size) {
+            return Err(VulkanError::TextureCreationError(String::from(
+                "A cubemap needs six RGBA faces of size x size pixels",
+            )));
+        }
+
+        let pixels = self.faces.concat();
+
+        let staging_buffer = BufferBuilder::new(self.context)
+            .with_type(BufferType::Staging)
+            .with_size(pixels.len() as vk::DeviceSize)
+            .build()?;
+
+        staging_buffer.copy_data(pixels.as_ptr() as *const c_void)?;
+
+        let (texture_image, texture_image_memory) = image::ImageBuilder::new(self.context)
+            .with_width(self.size)
+            .with_height(self.size)
+            .with_format(vk::Format::R8G8B8A8_UNORM)
+            .with_usage(vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED)
+            .with_array_layers(6)
+            .with_flags(vk::ImageCreateFlags::CUBE_COMPATIBLE)
+            .build()?;
+
+        image::transition_image_layout_range(
+            self.context,
+            texture_image,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            image::TransitionRange {
+                layer_count: 6,
+                ..Default::default()
+            },
+        )?;
 
-        let region = vk::BufferImageCopy::builder()
-            .buffer_offset(0)
-            .buffer_row_length(0)
-            .buffer_image_height(0)
-            .image_subresource(
-                vk::ImageSubresourceLayers::builder()
-                    .aspect_mask(vk::ImageAspectFlags::COLOR)
-                    .mip_level(0)
-                    .base_array_layer(0)
-                    .layer_count(1)
-                    .build(),
-            )
-            .image_offset(vk::Offset3D::builder().x(0).y(0).z(0).build())
-            .image_extent(
-                vk::Extent3D::builder()
-                    .width(width)
-                    .height(height)
-                    .depth(1)
-                    .build(),
-            )
-            .build();
+        let regions: Vec<vk::BufferImageCopy> = (0..6)
+            .map(|face| {
+                vk::BufferImageCopy::builder()
+                    .buffer_offset((face * face_size) as vk::DeviceSize)
+                    .buffer_row_length(0)
+                    .buffer_image_height(0)
+                    .image_subresource(
+                        vk::ImageSubresourceLayers::builder()
+                            .aspect_mask(vk::ImageAspectFlags::COLOR)
+                            .mip_level(0)
+                            .base_array_layer(face as u32)
+                            .layer_count(1)
+                            .build(),
+                    )
+                    .image_offset(vk::Offset3D::builder().x(0).y(0).z(0).build())
+                    .image_extent(
+                        vk::Extent3D::builder()
+                            .width(self.size)
+                            .height(self.size)
+                            .depth(1)
+                            .build(),
+                    )
+                    .build()
+            })
+            .collect();
 
+        let command_buffer = self.context.begin_single_time_commands()?;
         self.context.get_device().cmd_copy_buffer_to_image(
             command_buffer,
-            buffer,
-            image,
+            staging_buffer.get(),
+            texture_image,
             vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            &[region],
+            &regions,
         );
+        self.context.end_single_time_commands(command_buffer)?;
+
+        image::transition_image_layout_range(
+            self.context,
+            texture_image,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            image::TransitionRange {
+                layer_count: 6,
+                ..Default::default()
+            },
+        )?;
+
+        let texture_image_view = image::create_image_view_cube(
+            self.context,
+            texture_image,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageAspectFlags::COLOR,
+        )?;
+
+        let texture_sampler = self.context.get_sampler_cache().get_or_create(SamplerKey {
+            address_mode: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            ..Default::default()
+        })?;
+
+        let descriptor_image_info = descriptor_image_info(texture_image_view, &texture_sampler);
 
-        self.context.end_single_time_commands(command_buffer)
+        Ok(Texture {
+            device: Shared::clone(self.context.get_device()),
+            texture_image,
+            texture_image_memory,
+            texture_image_view,
+            texture_sampler,
+            descriptor_image_info,
+            extent: vk::Extent2D {
+                width: self.size,
+                height: self.size,
+            },
+            mip_levels: 1,
+            view_type: vk::ImageViewType::CUBE,
+        })
+    }
+}
+
+fn descriptor_image_info(image_view: vk::ImageView, sampler: &Sampler) -> vk::DescriptorImageInfo {
+    vk::DescriptorImageInfo::builder()
+        .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .image_view(image_view)
+        .sampler(sampler.get())
+        .build()
+}
+
+/// Copies `pixels` into one level of an image in `TRANSFER_DST_OPTIMAL` layout.
+fn upload_mip_level(
+    context: &VulkanContext,
+    image: vk::Image,
+    width: u32,
+    height: u32,
+    mip_level: u32,
+    pixels: &[u8],
+) -> Result<(), VulkanError> {
+    let image_size = (width * height * 4) as vk::DeviceSize;
+    if (pixels.len() as vk::DeviceSize) < image_size {
+        return Err(VulkanError::TextureCreationError(format!(
+            "Mip level {} needs {} bytes of RGBA pixels, got {}",
+            mip_level,
+            image_size,
+            pixels.len()
+        )));
     }
+
+    let staging_buffer = BufferBuilder::new(context)
+        .with_type(BufferType::Staging)
+        .with_size(image_size)
+        .build()?;
+
+    staging_buffer.copy_data(pixels.as_ptr() as *const c_void)?;
+
+    image::copy_buffer_to_image(
+        context,
+        staging_buffer.get(),
+        image,
+        vk::ImageSubresourceLayers::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .mip_level(mip_level)
+            .base_array_layer(0)
+            .layer_count(1)
+            .build(),
+        vk::Offset3D::builder().x(0).y(0).z(0).build(),
+        vk::Extent3D::builder()
+            .width(width)
+            .height(height)
+            .depth(1)
+            .build(),
+    )
 }
@@ -0,0 +1,128 @@
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_void;
+
+use ash::vk;
+
+use crate::buffer::{Buffer, BufferBuilder, BufferType};
+use crate::device::VulkanDevice;
+use crate::errors::VulkanError;
+use crate::vulkan_context::VulkanContext;
+use crate::Shared;
+
+pub struct UniformDescriptor<T> {
+    device: Shared<VulkanDevice>,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    buffer: Buffer,
+    phantom: PhantomData<T>,
+}
+
+impl<T> Drop for UniformDescriptor<T> {
+    fn drop(&mut self) {
+        self.device.destroy_descriptor_pool(self.descriptor_pool);
+        self.device
+            .destroy_descriptor_set_layout(self.descriptor_set_layout);
+    }
+}
+
+impl<T> UniformDescriptor<T> {
+    pub fn set(&self) -> vk::DescriptorSet {
+        self.descriptor_set
+    }
+
+    pub fn layout(&self) -> vk::DescriptorSetLayout {
+        self.descriptor_set_layout
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    pub fn update(&self, data: &T) -> Result<(), VulkanError> {
+        self.buffer.copy_data(data as *const T as *const c_void)
+    }
+}
+
+pub struct UniformDescriptorBuilder<'a, T> {
+    context: &'a VulkanContext,
+    phantom: PhantomData<T>,
+}
+
+impl<'a, T> UniformDescriptorBuilder<'a, T> {
+    pub fn new(context: &'a VulkanContext) -> Self {
+        UniformDescriptorBuilder {
+            context,
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn build(self) -> Result<UniformDescriptor<T>, VulkanError> {
+        let device = self.context.get_device();
+        let buffer_size = mem::size_of::<T>() as vk::DeviceSize;
+
+        let buffer = BufferBuilder::new(self.context)
+            .with_type(BufferType::Uniform)
+            .with_size(buffer_size)
+            .build()?;
+
+        let layout_bindings = [vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT)
+            .build()];
+
+        let layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .bindings(&layout_bindings)
+            .build();
+
+        let descriptor_set_layout = device.create_descriptor_set_layout(&layout_info)?;
+
+        let pool_sizes = [vk::DescriptorPoolSize::builder()
+            .ty(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_count(1)
+            .build()];
+
+        let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&pool_sizes)
+            .max_sets(1)
+            .build();
+
+        let descriptor_pool = device.create_descriptor_pool(&pool_info)?;
+
+        let set_layouts = [descriptor_set_layout];
+        let alloc_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(descriptor_pool)
+            .set_layouts(&set_layouts)
+            .build();
+
+        let descriptor_set = device.allocate_descriptor_sets(&alloc_info)?[0];
+
+        let buffer_infos = [vk::DescriptorBufferInfo::builder()
+            .buffer(buffer.get())
+            .offset(0)
+            .range(buffer_size)
+            .build()];
+
+        let descriptor_write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptor_set)
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .buffer_info(&buffer_infos)
+            .build();
+
+        device.update_descriptor_sets(&[descriptor_write]);
+
+        Ok(UniformDescriptor {
+            device: Shared::clone(device),
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_set,
+            buffer,
+            phantom: PhantomData,
+        })
+    }
+}
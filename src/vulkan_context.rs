@@ -1,47 +1,100 @@
-use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 use ash::vk;
+use raw_window_handle::HasRawWindowHandle;
 
+use crate::buffer::{Buffer, BufferBuilder, BufferType};
+use crate::color_resources::{ColorResources, ColorResourcesBuilder};
 use crate::command_buffers::{CommandBuffers, CommandBuffersBuilder};
-use crate::debug::DebugOptions;
+use crate::debug::{self, DebugCallback, DebugOptions};
 use crate::depth_resources::{DepthResources, DepthResourcesBuilder};
 use crate::device::{VulkanDevice, VulkanDeviceBuilder};
 use crate::errors::VulkanError;
 use crate::extensions::DeviceExtensions;
 use crate::features::Features;
 use crate::frame_buffer::{FrameBuffers, FrameBuffersBuilder};
+use crate::image;
 use crate::instance::{ApplicationInfo, VulkanInstance, VulkanInstanceBuilder};
-use crate::physical_device::{PhysicalDevice, PhysicalDeviceBuilder};
+use crate::memory_allocator::MemoryAllocator;
+use crate::mesh::{Mesh, MeshBuilder};
+use crate::physical_device::{
+    PhysicalDevice, PhysicalDeviceBuilder, PhysicalDeviceInfo, PhysicalDeviceScorer,
+};
 use crate::render_pass::{RenderPass, RenderPassBuilder};
+use crate::sampler::SamplerCache;
+use crate::shader_module::ShaderModule;
 use crate::surface::{Surface, SurfaceBuilder};
-use crate::swapchain::{Swapchain, SwapchainBuilder};
-use crate::windows::Win32Window;
+use crate::swapchain::{Swapchain, SwapchainBuilder, SwapchainStatus};
+use crate::uniform_descriptor::{UniformDescriptor, UniformDescriptorBuilder};
+use crate::windows::PlatformWindow;
+use crate::Shared;
+use std::cell::Cell;
+use std::marker::PhantomData;
 use std::mem;
+use std::ptr;
+
+const SHUTDOWN_TIMEOUT: u64 = 5_000_000_000;
+const MAX_UPDATE_BUFFER_SIZE: usize = 65536;
+
+struct RenderTargetOverride {
+    frame_buffers: FrameBuffers,
+    render_pass: RenderPass,
+    _depth_resources: DepthResources,
+    _color_resources: Option<ColorResources>,
+    image: vk::Image,
+    image_view: vk::ImageView,
+    extent: vk::Extent2D,
+    format: vk::Format,
+}
 
 pub struct VulkanContext {
+    render_target_override: Option<RenderTargetOverride>,
     frame_buffers: Option<FrameBuffers>,
     render_pass: Option<RenderPass>,
     depth_resources: Option<DepthResources>,
+    color_resources: Option<ColorResources>,
     swapchain: Option<Swapchain>,
+    sampler_cache: SamplerCache,
+    memory_allocator: Option<Shared<MemoryAllocator>>,
     command_buffers: CommandBuffers,
-    device: Rc<VulkanDevice>,
+    device: Shared<VulkanDevice>,
     physical_device: PhysicalDevice,
     surface: Surface,
-    instance: Rc<VulkanInstance>,
+    instance: Shared<VulkanInstance>,
+    extensions: Vec<DeviceExtensions>,
+    features: Features,
+    memory_block_size: Option<vk::DeviceSize>,
     frame_index: usize,
     frames_count: usize,
+    command_buffers_per_frame: u32,
+    validation_error_sink: Option<Arc<AtomicUsize>>,
     back_buffer_index: usize,
     clear_value: [f32; 4],
+    clear_values: Vec<vk::ClearValue>,
+    last_present_result: vk::Result,
+    present_mode: Option<vk::PresentModeKHR>,
+    desired_format: Option<vk::SurfaceFormatKHR>,
+    sample_count: vk::SampleCountFlags,
+    image_array_layers: u32,
+    view_mask: u32,
+    low_memory_profile: bool,
+    is_shut_down: bool,
+    // The command pool and queue used through `&self` are not locked, so the context must not
+    // be shared between threads.
+    _not_sync: PhantomData<Cell<()>>,
 }
 
 impl Drop for VulkanContext {
     fn drop(&mut self) {
-        self.device.queue_wait_idle().unwrap();
+        if let Err(err) = self.teardown() {
+            log::error!("Failed to shut down the Vulkan context: {}", err);
+        }
     }
 }
 
 impl VulkanContext {
-    pub fn get_instance(&self) -> &Rc<VulkanInstance> {
+    pub fn get_instance(&self) -> &Shared<VulkanInstance> {
         &self.instance
     }
 
@@ -53,7 +106,7 @@ impl VulkanContext {
         &self.physical_device
     }
 
-    pub fn get_device(&self) -> &Rc<VulkanDevice> {
+    pub fn get_device(&self) -> &Shared<VulkanDevice> {
         &self.device
     }
 
@@ -65,26 +118,149 @@ impl VulkanContext {
         self.depth_resources.as_ref().unwrap()
     }
 
+    pub fn get_color_resources(&self) -> Option<&ColorResources> {
+        self.color_resources.as_ref()
+    }
+
+    pub fn get_sample_count(&self) -> vk::SampleCountFlags {
+        self.sample_count
+    }
+
+    pub fn get_image_array_layers(&self) -> u32 {
+        self.image_array_layers
+    }
+
+    pub fn get_sampler_cache(&self) -> &SamplerCache {
+        &self.sampler_cache
+    }
+
+    /// Validation errors reported so far, always 0 without a validation error sink.
+    pub fn get_validation_error_count(&self) -> usize {
+        self.validation_error_sink
+            .as_ref()
+            .map_or(0, |sink| sink.load(Ordering::SeqCst))
+    }
+
+    pub fn get_memory_allocator(&self) -> Option<&Shared<MemoryAllocator>> {
+        self.memory_allocator.as_ref()
+    }
+
     pub fn get_render_pass(&self) -> &RenderPass {
         self.render_pass.as_ref().unwrap()
     }
 
+    pub fn get_current_render_pass(&self) -> &RenderPass {
+        match &self.render_target_override {
+            Some(render_target) => &render_target.render_pass,
+            None => self.get_render_pass(),
+        }
+    }
+
+    pub fn command_pool(&self) -> vk::CommandPool {
+        self.command_buffers.pool()
+    }
+
     pub fn get_current_command_buffer(&self) -> vk::CommandBuffer {
         self.command_buffers.get(self.frame_index)
     }
 
+    /// Additional primary command buffer of the current frame, submitted after the main one.
+    /// These are not secondary level buffers, see `SecondaryCommandBuffers` for those.
+    pub fn get_extra_command_buffer(&self, slot: usize) -> vk::CommandBuffer {
+        self.command_buffers
+            .get_extra_command_buffer(self.frame_index, slot)
+    }
+
     pub fn get_current_back_buffer(&self) -> vk::Image {
-        self.swapchain
-            .as_ref()
-            .unwrap()
-            .get_image(self.back_buffer_index)
+        match &self.render_target_override {
+            Some(render_target) => render_target.image,
+            None => self
+                .swapchain
+                .as_ref()
+                .unwrap()
+                .get_image(self.back_buffer_index),
+        }
     }
 
     pub fn get_current_back_buffer_view(&self) -> vk::ImageView {
-        self.swapchain
-            .as_ref()
-            .unwrap()
-            .get_image_view(self.back_buffer_index)
+        match &self.render_target_override {
+            Some(render_target) => render_target.image_view,
+            None => self
+                .swapchain
+                .as_ref()
+                .unwrap()
+                .get_image_view(self.back_buffer_index),
+        }
+    }
+
+    pub fn get_current_frame_buffer(&self) -> vk::Framebuffer {
+        match &self.render_target_override {
+            Some(render_target) => render_target.frame_buffers.get(0),
+            None => self
+                .frame_buffers
+                .as_ref()
+                .unwrap()
+                .get(self.back_buffer_index),
+        }
+    }
+
+    /// Renders into `image` instead of the swapchain. The pass leaves it in `final_layout`, e.g.
+    /// `TRANSFER_SRC_OPTIMAL` to read it back.
+    pub fn render_target_override(
+        &mut self,
+        image: vk::Image,
+        image_view: vk::ImageView,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        final_layout: vk::ImageLayout,
+    ) -> Result<(), VulkanError> {
+        self.clear_render_target_override()?;
+
+        let depth_resources = self.create_depth_resources(extent.width, extent.height)?;
+        let color_resources = self.create_color_resources(extent.width, extent.height, format)?;
+
+        let render_pass = RenderPassBuilder::new(self)
+            .with_color_format(format)
+            .with_depth_format(depth_resources.get_format())
+            .with_sample_count(self.sample_count)
+            .with_color_final_layout(final_layout)
+            .build()?;
+
+        let mut frame_buffers = FrameBuffersBuilder::new(self)
+            .with_width(extent.width)
+            .with_height(extent.height)
+            .with_render_pass(render_pass.get())
+            .with_color_views(vec![image_view])
+            .with_depth_view(depth_resources.get_image_view());
+
+        if let Some(color_resources) = &color_resources {
+            frame_buffers = frame_buffers.with_msaa_color_view(color_resources.get_image_view());
+        }
+
+        let frame_buffers = frame_buffers.build()?;
+
+        self.render_target_override = Some(RenderTargetOverride {
+            frame_buffers,
+            render_pass,
+            _depth_resources: depth_resources,
+            _color_resources: color_resources,
+            image,
+            image_view,
+            extent,
+            format,
+        });
+
+        Ok(())
+    }
+
+    /// Waits for the device to be idle, as frames in flight may still use the override.
+    pub fn clear_render_target_override(&mut self) -> Result<(), VulkanError> {
+        if self.render_target_override.is_some() {
+            self.device.device_wait_idle()?;
+            self.render_target_override = None;
+        }
+
+        Ok(())
     }
 
     pub fn get_clear_value(&self) -> [f32; 4] {
@@ -95,15 +271,44 @@ impl VulkanContext {
         self.clear_value = clear_value;
     }
 
-    pub fn frame_begin(&mut self) -> Result<(), VulkanError> {
+    pub fn get_clear_values(&self) -> &[vk::ClearValue] {
+        &self.clear_values
+    }
+
+    pub fn set_clear_values(&mut self, clear_values: Vec<vk::ClearValue>) {
+        self.clear_values = clear_values;
+    }
+
+    pub fn is_frame_complete(&self) -> Result<bool, VulkanError> {
+        self.command_buffers.is_frame_complete(self.frame_index)
+    }
+
+    pub fn reset_frame_fences(&self, frame_indices: &[usize]) -> Result<(), VulkanError> {
+        self.command_buffers.reset_fences(frame_indices)
+    }
+
+    pub fn frame_begin(&mut self) -> Result<SwapchainStatus, VulkanError> {
         self.command_buffers.wait_for_fence(self.frame_index)?;
 
-        self.back_buffer_index = self.swapchain.as_ref().unwrap().acquire_next_image(
+        let swapchain = self.swapchain.as_ref().ok_or_else(|| {
+            VulkanError::SwapchainError(String::from(
+                "No swapchain, call resize or try_recover first",
+            ))
+        })?;
+        let (back_buffer_index, status) = swapchain.acquire_next_image(
             self.command_buffers
                 .get_present_complete_semaphore(self.frame_index),
         )?;
 
-        self.command_buffers.begin_command_buffer(self.frame_index)
+        if status == SwapchainStatus::OutOfDate {
+            return Ok(status);
+        }
+
+        self.back_buffer_index = back_buffer_index;
+        self.command_buffers
+            .begin_command_buffer(self.frame_index)?;
+
+        Ok(status)
     }
 
     pub fn frame_end(&self) -> Result<(), VulkanError> {
@@ -112,52 +317,342 @@ impl VulkanContext {
         self.command_buffers.queue_submit(self.frame_index)
     }
 
-    pub fn frame_present(&mut self) -> Result<(), VulkanError> {
-        self.swapchain.as_ref().unwrap().queue_present(
-            self.command_buffers
-                .get_render_complete_semaphore(self.frame_index),
-            self.back_buffer_index as u32,
-        )?;
+    pub fn frame_present(&mut self) -> Result<SwapchainStatus, VulkanError> {
+        let wait_semaphore = self
+            .command_buffers
+            .get_render_complete_semaphore(self.frame_index);
+
+        self.last_present_result = self
+            .swapchain
+            .as_ref()
+            .unwrap()
+            .queue_present_result(wait_semaphore, self.back_buffer_index as u32);
+
+        let status = Swapchain::present_status(self.last_present_result)?;
+
         self.frame_index = (self.frame_index + 1) % self.frames_count;
-        Ok(())
+        Ok(status)
     }
 
-    pub fn begin_render_pass(&self) {
-        let clear_color = vk::ClearValue {
-            color: vk::ClearColorValue {
-                float32: self.clear_value,
-            },
+    /// Ends, submits and presents the current frame, then blocks until the GPU and the present
+    /// queue are done with it. Meant for single frame in flight, latency-sensitive setups.
+    pub fn render_and_present_sync(&mut self) -> Result<SwapchainStatus, VulkanError> {
+        let frame_index = self.frame_index;
+
+        self.frame_end()?;
+        let status = self.frame_present()?;
+
+        self.command_buffers.wait_for_fence(frame_index)?;
+        self.device.present_queue_wait_idle()?;
+
+        Ok(status)
+    }
+
+    fn get_attachment_clear_color(&self) -> [f32; 4] {
+        if !image::is_srgb_format(self.get_current_color_format()) {
+            return self.clear_value;
+        }
+
+        // sRGB attachments encode on write, so the sRGB clear color is linearized first.
+        let to_linear = |value: f32| {
+            if value <= 0.040_45 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            }
         };
-        let clear_depth = vk::ClearValue {
-            depth_stencil: vk::ClearDepthStencilValue::builder()
-                .depth(1.0)
-                .stencil(0)
-                .build(),
+
+        [
+            to_linear(self.clear_value[0]),
+            to_linear(self.clear_value[1]),
+            to_linear(self.clear_value[2]),
+            self.clear_value[3],
+        ]
+    }
+
+    pub fn get_last_present_result(&self) -> vk::Result {
+        self.last_present_result
+    }
+
+    pub fn get_current_extent(&self) -> vk::Extent2D {
+        match &self.render_target_override {
+            Some(render_target) => render_target.extent,
+            None => self.get_swapchain().get_extent(),
+        }
+    }
+
+    pub fn default_viewport(&self) -> vk::Viewport {
+        let extent = self.get_current_extent();
+        vk::Viewport::builder()
+            .x(0.0)
+            .y(0.0)
+            .width(extent.width as f32)
+            .height(extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0)
+            .build()
+    }
+
+    pub fn default_scissor(&self) -> vk::Rect2D {
+        vk::Rect2D::builder()
+            .extent(self.get_current_extent())
+            .build()
+    }
+
+    pub fn get_current_color_format(&self) -> vk::Format {
+        match &self.render_target_override {
+            Some(render_target) => render_target.format,
+            None => self.get_swapchain().get_format().format,
+        }
+    }
+
+    pub fn begin_render_pass(&self) {
+        self.begin_render_pass_with_contents(vk::SubpassContents::INLINE);
+    }
+
+    /// Begins the render pass for `execute_secondary_command_buffers`, no draw can be recorded
+    /// inline in the first subpass.
+    pub fn begin_render_pass_for_secondary_command_buffers(&self) {
+        self.begin_render_pass_with_contents(vk::SubpassContents::SECONDARY_COMMAND_BUFFERS);
+    }
+
+    fn begin_render_pass_with_contents(&self, contents: vk::SubpassContents) {
+        let clear_values = if self.clear_values.is_empty() {
+            let clear_color = vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: self.get_attachment_clear_color(),
+                },
+            };
+            let clear_depth = vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue::builder()
+                    .depth(1.0)
+                    .stencil(0)
+                    .build(),
+            };
+            vec![clear_color, clear_depth]
+        } else {
+            self.clear_values.clone()
         };
+
         let info = vk::RenderPassBeginInfo::builder()
-            .render_pass(self.render_pass.as_ref().unwrap().get())
-            .framebuffer(
-                self.frame_buffers
-                    .as_ref()
-                    .unwrap()
-                    .get(self.back_buffer_index),
-            )
+            .render_pass(self.get_current_render_pass().get())
+            .framebuffer(self.get_current_frame_buffer())
             .render_area(
                 vk::Rect2D::builder()
-                    .extent(self.swapchain.as_ref().unwrap().get_extent())
+                    .extent(self.get_current_extent())
                     .build(),
             )
-            .clear_values(&[clear_color, clear_depth])
+            .clear_values(&clear_values)
             .build();
 
+        self.device.cmd_begin_render_pass(
+            self.command_buffers.get(self.frame_index),
+            &info,
+            contents,
+        );
+    }
+
+    /// Replays secondary command buffers recorded against the current render pass and frame
+    /// buffer into the current command buffer.
+    pub fn execute_secondary_command_buffers(&self, command_buffers: &[vk::CommandBuffer]) {
         self.device
-            .cmd_begin_render_pass(self.command_buffers.get(self.frame_index), &info);
+            .cmd_execute_commands(self.command_buffers.get(self.frame_index), command_buffers);
     }
+
     pub fn end_render_pass(&self) {
         self.device
             .cmd_end_render_pass(self.command_buffers.get(self.frame_index));
     }
 
+    /// Writes `data` at the start of `buffer` from the current command buffer, without a staging
+    /// buffer, and makes it visible to the reads in `dst_stage`, e.g. `VERTEX_INPUT` with
+    /// `VERTEX_ATTRIBUTE_READ` or `COMPUTE_SHADER` with `SHADER_READ`. Must be recorded outside
+    /// of a render pass; `data` is limited to 65536 bytes and a multiple of 4 bytes, and `buffer`
+    /// needs `TRANSFER_DST` usage.
+    // usize::is_multiple_of would need Rust 1.87.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn update_small_buffer(
+        &self,
+        buffer: &Buffer,
+        data: &[u8],
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    ) -> Result<(), VulkanError> {
+        if data.len() > MAX_UPDATE_BUFFER_SIZE || data.len() % 4 != 0 {
+            return Err(VulkanError::BufferError(format!(
+                "Cannot update {} bytes inline, the size must be a multiple of 4 up to {}",
+                data.len(),
+                MAX_UPDATE_BUFFER_SIZE
+            )));
+        }
+        if data.len() as vk::DeviceSize > buffer.get_size() {
+            return Err(VulkanError::BufferError(format!(
+                "Update of {} bytes overflows buffer of {} bytes",
+                data.len(),
+                buffer.get_size()
+            )));
+        }
+        if !buffer
+            .get_usage()
+            .contains(vk::BufferUsageFlags::TRANSFER_DST)
+        {
+            return Err(VulkanError::BufferError(String::from(
+                "Buffer updates need TRANSFER_DST usage",
+            )));
+        }
+
+        let command_buffer = self.get_current_command_buffer();
+        buffer.update_buffer(command_buffer, data);
+
+        let barrier = vk::BufferMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(dst_access)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(buffer.get())
+            .offset(0)
+            .size(data.len() as vk::DeviceSize)
+            .build();
+        self.device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            dst_stage,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[barrier],
+            &[],
+        );
+
+        Ok(())
+    }
+
+    /// Copies a region of a color image back to the host, e.g. the pixel under the cursor for
+    /// picking. `T` is one texel of `format`; the image is left in `layout`.
+    pub fn read_image_region<T: Copy>(
+        &self,
+        image: vk::Image,
+        format: vk::Format,
+        layout: vk::ImageLayout,
+        offset: vk::Offset3D,
+        extent: vk::Extent3D,
+    ) -> Result<Vec<T>, VulkanError> {
+        if extent.width == 0 || extent.height == 0 || extent.depth == 0 {
+            return Err(VulkanError::ImageCreationError(String::from(
+                "Cannot read back an empty image region",
+            )));
+        }
+        let texel_size = image::texel_size(format).ok_or_else(|| {
+            VulkanError::ImageCreationError(format!("Cannot read back {:?} images", format))
+        })?;
+        if mem::size_of::<T>() != texel_size as usize {
+            return Err(VulkanError::ImageCreationError(format!(
+                "{:?} texels are {} bytes, not {}",
+                format,
+                texel_size,
+                mem::size_of::<T>()
+            )));
+        }
+
+        let texel_count = extent.width as usize * extent.height as usize * extent.depth as usize;
+        let size = texel_count * texel_size as usize;
+        let staging_buffer = BufferBuilder::new(self)
+            .with_type(BufferType::Staging)
+            .with_usage_flags(vk::BufferUsageFlags::TRANSFER_DST)
+            .with_size(size as vk::DeviceSize)
+            .build()?;
+
+        let subresource_range = vk::ImageSubresourceRange::builder()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .base_mip_level(0)
+            .level_count(1)
+            .base_array_layer(0)
+            .layer_count(1)
+            .build();
+        let copy_layout = if layout == vk::ImageLayout::GENERAL {
+            layout
+        } else {
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+        };
+
+        let command_buffer = self.begin_single_time_commands()?;
+
+        let barrier = vk::ImageMemoryBarrier::builder()
+            .old_layout(layout)
+            .new_layout(copy_layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range)
+            .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .build();
+        self.device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier],
+        );
+
+        let region = vk::BufferImageCopy::builder()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(
+                vk::ImageSubresourceLayers::builder()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .mip_level(0)
+                    .base_array_layer(0)
+                    .layer_count(1)
+                    .build(),
+            )
+            .image_offset(offset)
+            .image_extent(extent)
+            .build();
+        self.device.cmd_copy_image_to_buffer(
+            command_buffer,
+            image,
+            copy_layout,
+            staging_buffer.get(),
+            &[region],
+        );
+
+        let barrier = vk::ImageMemoryBarrier::builder()
+            .old_layout(copy_layout)
+            .new_layout(layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(subresource_range)
+            .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .dst_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+            .build();
+        self.device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[barrier],
+        );
+
+        self.end_single_time_commands(command_buffer)?;
+
+        let data = staging_buffer.map_persistent()?;
+        staging_buffer.invalidate()?;
+
+        let mut texels = Vec::with_capacity(texel_count);
+        unsafe {
+            ptr::copy_nonoverlapping(data as *const u8, texels.as_mut_ptr() as *mut u8, size);
+            texels.set_len(texel_count);
+        }
+
+        Ok(texels)
+    }
+
     pub fn begin_single_time_commands(&self) -> Result<vk::CommandBuffer, VulkanError> {
         self.command_buffers.begin_single_time_commands()
     }
@@ -170,8 +665,227 @@ impl VulkanContext {
             .end_single_time_commands(command_buffer)
     }
 
+    pub fn end_single_time_commands_async(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        signal_semaphore: vk::Semaphore,
+        fence: vk::Fence,
+    ) -> Result<(), VulkanError> {
+        self.command_buffers
+            .end_single_time_commands_async(command_buffer, signal_semaphore, fence)
+    }
+
+    pub fn end_single_time_commands_with_fence(
+        &self,
+        command_buffer: vk::CommandBuffer,
+    ) -> Result<vk::Fence, VulkanError> {
+        self.command_buffers
+            .end_single_time_commands_with_fence(command_buffer)
+    }
+
+    pub fn free_single_time_commands(&self, command_buffer: vk::CommandBuffer) {
+        self.command_buffers
+            .free_single_time_commands(command_buffer)
+    }
+
+    pub fn create_uniform_descriptor<T>(&self) -> Result<UniformDescriptor<T>, VulkanError> {
+        UniformDescriptorBuilder::new(self).build()
+    }
+
+    pub fn create_mesh<V: Copy>(
+        &self,
+        vertices: &[V],
+        indices: &[u32],
+    ) -> Result<Mesh, VulkanError> {
+        MeshBuilder::new(self)
+            .with_vertices(vertices)
+            .with_indices(indices)
+            .build()
+    }
+
+    pub fn shader_needs_reload(&self, shader: &ShaderModule) -> bool {
+        shader.is_modified()
+    }
+
+    /// Reloads every shader whose file changed on disk, then calls `rebuild_pipelines` so the
+    /// pipelines using them can be recreated. Returns whether anything was reloaded.
+    pub fn hot_reload_shaders<F>(
+        &self,
+        shaders: &mut [&mut ShaderModule],
+        rebuild_pipelines: F,
+    ) -> Result<bool, VulkanError>
+    where
+        F: FnOnce(&VulkanContext) -> Result<(), VulkanError>,
+    {
+        if !shaders
+            .iter()
+            .any(|shader| self.shader_needs_reload(shader))
+        {
+            return Ok(false);
+        }
+
+        self.device.device_wait_idle()?;
+
+        for shader in shaders.iter_mut() {
+            if shader.is_modified() {
+                shader.reload()?;
+            }
+        }
+
+        rebuild_pipelines(self)?;
+
+        Ok(true)
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) -> Result<(), VulkanError> {
-        self.device.queue_wait_idle()?;
+        self.recreate_swapchain(width, height)
+    }
+
+    /// Whether the present queue family can still present to the window surface.
+    pub fn supports_presentation(&self) -> bool {
+        self.physical_device.supports_presentation(&self.surface)
+    }
+
+    pub fn current_surface_capabilities(&self) -> Result<vk::SurfaceCapabilitiesKHR, VulkanError> {
+        self.surface
+            .get_physical_device_surface_capabilities(self.physical_device.get())
+    }
+
+    /// Does nothing while the surface has a zero extent, e.g. when the window is minimized. The
+    /// current swapchain and its attachments are kept until their replacements are created.
+    pub fn recreate_swapchain(&mut self, width: u32, height: u32) -> Result<(), VulkanError> {
+        let capabilities = self.current_surface_capabilities()?;
+        let width = width.clamp(
+            capabilities.min_image_extent.width,
+            capabilities.max_image_extent.width,
+        );
+        let height = height.clamp(
+            capabilities.min_image_extent.height,
+            capabilities.max_image_extent.height,
+        );
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+
+        self.device.device_wait_idle()?;
+
+        let swapchain = self.create_swapchain(self.swapchain.as_ref(), width, height)?;
+        let extent = swapchain.get_extent();
+        let color_format = swapchain.get_format().format;
+
+        let depth_resources = self.create_depth_resources(extent.width, extent.height)?;
+        let color_resources =
+            self.create_color_resources(extent.width, extent.height, color_format)?;
+
+        let old_format = self
+            .swapchain
+            .as_ref()
+            .map(|swapchain| swapchain.get_format().format);
+        let render_pass = if self.render_pass.is_none() || old_format != Some(color_format) {
+            Some(self.create_render_pass(color_format, depth_resources.get_format())?)
+        } else {
+            None
+        };
+
+        let frame_buffers = self.create_frame_buffers(
+            &swapchain,
+            &depth_resources,
+            color_resources.as_ref(),
+            render_pass
+                .as_ref()
+                .unwrap_or_else(|| self.get_render_pass()),
+        )?;
+
+        self.frame_buffers = Some(frame_buffers);
+        if let Some(render_pass) = render_pass {
+            self.render_pass = Some(render_pass);
+        }
+        self.color_resources = color_resources;
+        self.depth_resources = Some(depth_resources);
+        self.swapchain = Some(swapchain);
+
+        Ok(())
+    }
+
+    /// Rebuilds the logical device and everything the context created from it after a call
+    /// failed with `ERROR_DEVICE_LOST`. The surface and physical device are kept. Resources
+    /// created by the application belong to the lost device and must be recreated as well.
+    ///
+    /// The context is left untouched when the new device cannot be created. When only the
+    /// swapchain fails, `frame_begin` returns an error until `resize` or `try_recover` succeeds.
+    pub fn try_recover(&mut self) -> Result<(), VulkanError> {
+        let extent = match &self.swapchain {
+            Some(swapchain) => swapchain.get_extent(),
+            None => self.current_surface_capabilities()?.current_extent,
+        };
+
+        let device = Shared::new(
+            VulkanDeviceBuilder::new(Shared::clone(&self.instance), &self.physical_device)
+                .with_extensions(&self.extensions)
+                .with_features(self.features)
+                .build()?,
+        );
+        let command_buffers =
+            CommandBuffersBuilder::new(&self.physical_device, Shared::clone(&device))
+                .with_frames_count(self.frames_count as u32)
+                .with_command_buffers_per_frame(self.command_buffers_per_frame)
+                .build()?;
+
+        // The lost device cannot be waited on, so its objects are released right away. The
+        // swapchain has to go before a new one can be created for the surface; the other
+        // objects are replaced by `recreate_swapchain`.
+        self.render_target_override = None;
+        self.swapchain = None;
+
+        self.command_buffers = command_buffers;
+        self.sampler_cache = SamplerCache::new(
+            Shared::clone(&device),
+            self.physical_device.get_max_sampler_anisotropy(),
+        );
+        self.memory_allocator = self.memory_block_size.map(|block_size| {
+            Shared::new(MemoryAllocator::new(
+                Shared::clone(&device),
+                block_size,
+                self.physical_device.get_limits().buffer_image_granularity,
+            ))
+        });
+        self.device = device;
+
+        self.frame_index = 0;
+        self.back_buffer_index = 0;
+        self.last_present_result = vk::Result::SUCCESS;
+        self.is_shut_down = false;
+
+        self.recreate_swapchain(extent.width, extent.height)
+    }
+
+    pub fn shutdown(mut self) -> Result<(), VulkanError> {
+        self.teardown()
+    }
+
+    fn teardown(&mut self) -> Result<(), VulkanError> {
+        if self.is_shut_down {
+            return Ok(());
+        }
+
+        // The bounded fence wait goes first so that a wedged GPU cannot hang the shutdown in
+        // device_wait_idle.
+        if !self
+            .command_buffers
+            .wait_all_fences_timeout(SHUTDOWN_TIMEOUT)?
+        {
+            return Err(VulkanError::DeviceError(
+                "wait_all_fences_timeout",
+                vk::Result::TIMEOUT,
+            ));
+        }
+
+        self.device.device_wait_idle()?;
+        self.is_shut_down = true;
+
+        if let Some(render_target) = self.render_target_override.take() {
+            mem::drop(render_target);
+        }
 
         if let Some(frame_buffers) = self.frame_buffers.take() {
             mem::drop(frame_buffers);
@@ -185,30 +899,43 @@ impl VulkanContext {
             mem::drop(depth_resources);
         }
 
-        let old_swapchain = self.swapchain.take();
-        self.swapchain = Some(self.create_swapchain(old_swapchain, width, height)?);
-
-        self.depth_resources = Some(self.create_depth_resources(width, height)?);
-
-        self.render_pass = Some(self.create_render_pass()?);
+        if let Some(color_resources) = self.color_resources.take() {
+            mem::drop(color_resources);
+        }
 
-        self.frame_buffers = Some(self.create_frame_buffers(width, height)?);
+        if let Some(swapchain) = self.swapchain.take() {
+            mem::drop(swapchain);
+        }
 
         Ok(())
     }
 
     fn create_swapchain(
-        &mut self,
-        old_swapchain: Option<Swapchain>,
+        &self,
+        old_swapchain: Option<&Swapchain>,
         width: u32,
         height: u32,
     ) -> Result<Swapchain, VulkanError> {
-        SwapchainBuilder::new(self)
+        let mut builder = SwapchainBuilder::new(self)
             .with_old_swapchain(old_swapchain)
             .with_width(width)
             .with_height(height)
             .with_frames_count(self.frames_count as u32)
-            .build()
+            .with_image_array_layers(self.image_array_layers);
+
+        if let Some(present_mode) = self.present_mode {
+            builder = builder.with_present_mode(present_mode);
+        }
+
+        if let Some(desired_format) = self.desired_format {
+            builder = builder.with_desired_format(desired_format);
+        }
+
+        if self.low_memory_profile {
+            builder = builder.with_image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT);
+        }
+
+        builder.build()
     }
 
     fn create_depth_resources(
@@ -219,29 +946,91 @@ impl VulkanContext {
         DepthResourcesBuilder::new(self)
             .with_width(width)
             .with_height(height)
+            .with_samples(self.sample_count)
+            .with_low_precision(self.low_memory_profile)
+            .with_layers(self.image_array_layers)
             .build()
     }
 
-    fn create_render_pass(&self) -> Result<RenderPass, VulkanError> {
-        RenderPassBuilder::new(self).build()
-    }
+    fn create_color_resources(
+        &self,
+        width: u32,
+        height: u32,
+        format: vk::Format,
+    ) -> Result<Option<ColorResources>, VulkanError> {
+        if self.sample_count == vk::SampleCountFlags::TYPE_1 {
+            return Ok(None);
+        }
 
-    fn create_frame_buffers(&self, width: u32, height: u32) -> Result<FrameBuffers, VulkanError> {
-        FrameBuffersBuilder::new(self)
+        ColorResourcesBuilder::new(self)
             .with_width(width)
             .with_height(height)
-            .with_frames_count(self.frames_count as u32)
+            .with_format(format)
+            .with_samples(self.sample_count)
             .build()
+            .map(Some)
+    }
+
+    fn create_render_pass(
+        &self,
+        color_format: vk::Format,
+        depth_format: vk::Format,
+    ) -> Result<RenderPass, VulkanError> {
+        RenderPassBuilder::new(self)
+            .with_color_format(color_format)
+            .with_depth_format(depth_format)
+            .with_sample_count(self.sample_count)
+            .with_multiview(self.view_mask)
+            .build()
+    }
+
+    fn create_frame_buffers(
+        &self,
+        swapchain: &Swapchain,
+        depth_resources: &DepthResources,
+        color_resources: Option<&ColorResources>,
+        render_pass: &RenderPass,
+    ) -> Result<FrameBuffers, VulkanError> {
+        let extent = swapchain.get_extent();
+        let mut builder = FrameBuffersBuilder::new(self)
+            .with_width(extent.width)
+            .with_height(extent.height)
+            .with_render_pass(render_pass.get())
+            .with_color_views(swapchain.get_image_views().to_vec())
+            .with_depth_view(depth_resources.get_image_view())
+            .with_layers(self.image_array_layers)
+            .with_view_mask(self.view_mask);
+
+        if let Some(color_resources) = color_resources {
+            builder = builder.with_msaa_color_view(color_resources.get_image_view());
+        }
+
+        builder.build()
     }
 }
 
 pub struct VulkanContextBuilder {
     application_info: ApplicationInfo,
     debug_options: DebugOptions,
-    window: Win32Window,
+    window: Option<PlatformWindow>,
     extensions: Vec<DeviceExtensions>,
     features: Features,
     frames_count: u32,
+    command_buffers_per_frame: u32,
+    present_mode: Option<vk::PresentModeKHR>,
+    desired_format: Option<vk::SurfaceFormatKHR>,
+    sample_count: vk::SampleCountFlags,
+    image_array_layers: u32,
+    view_mask: u32,
+    low_memory_profile: bool,
+    memory_block_size: Option<vk::DeviceSize>,
+    scorer: Option<PhysicalDeviceScorer>,
+    queue_family: Option<u32>,
+    preferred_device_type: Option<vk::PhysicalDeviceType>,
+    device_name_filter: Option<String>,
+    physical_device_index: Option<usize>,
+    debug_callback: Option<DebugCallback>,
+    validation_error_sink: Option<Arc<AtomicUsize>>,
 }
 
 impl Default for VulkanContextBuilder {
@@ -249,10 +1038,25 @@ impl Default for VulkanContextBuilder {
         VulkanContextBuilder {
             application_info: ApplicationInfo::default(),
             debug_options: DebugOptions::default(),
-            window: Win32Window::default(),
+            window: None,
             features: Features::default(),
             extensions: vec![],
             frames_count: 2,
+            command_buffers_per_frame: 1,
+            present_mode: None,
+            desired_format: None,
+            sample_count: vk::SampleCountFlags::TYPE_1,
+            image_array_layers: 1,
+            view_mask: 0,
+            low_memory_profile: false,
+            memory_block_size: None,
+            scorer: None,
+            queue_family: None,
+            preferred_device_type: None,
+            device_name_filter: None,
+            physical_device_index: None,
+            debug_callback: None,
+            validation_error_sink: None,
         }
     }
 }
@@ -272,8 +1076,32 @@ impl VulkanContextBuilder {
         self
     }
 
-    pub fn with_window(mut self, window: Win32Window) -> Self {
-        self.window = window;
+    pub fn with_debug_callback(mut self, debug_callback: DebugCallback) -> Self {
+        self.debug_callback = Some(debug_callback);
+        self
+    }
+
+    /// Counts every validation error into `sink`, e.g. to fail a test after a frame. Messages
+    /// are still forwarded to the debug callback or logged. Enables the validation layer with
+    /// at least error severity and validation messages on top of the debug options.
+    pub fn with_validation_error_sink(mut self, sink: Arc<AtomicUsize>) -> Self {
+        self.validation_error_sink = Some(sink);
+        self
+    }
+
+    pub fn with_window<W: Into<PlatformWindow>>(mut self, window: W) -> Self {
+        self.window = Some(window.into());
+        self
+    }
+
+    pub fn with_raw_window_handle<W: HasRawWindowHandle>(
+        mut self,
+        window: &W,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        self.window =
+            PlatformWindow::from_raw_window_handle(window.raw_window_handle(), width, height);
         self
     }
 
@@ -288,69 +1116,259 @@ impl VulkanContextBuilder {
     }
 
     pub fn with_frames_count(mut self, frames_count: u32) -> Self {
-        self.frames_count = frames_count;
+        self.frames_count = frames_count.max(1);
         self
     }
 
-    pub fn build(self) -> Result<VulkanContext, VulkanError> {
-        let instance = Rc::new(self.create_instance()?);
+    pub fn with_command_buffers_per_frame(mut self, command_buffers_per_frame: u32) -> Self {
+        self.command_buffers_per_frame = command_buffers_per_frame;
+        self
+    }
+
+    pub fn with_present_mode(mut self, present_mode: vk::PresentModeKHR) -> Self {
+        self.present_mode = Some(present_mode);
+        self
+    }
+
+    pub fn with_desired_format(mut self, desired_format: vk::SurfaceFormatKHR) -> Self {
+        self.desired_format = Some(desired_format);
+        self
+    }
+
+    /// Requests multisampled rendering. The count is clamped to the highest one the
+    /// physical device supports for both color and depth framebuffer attachments.
+    pub fn with_sample_count(mut self, sample_count: vk::SampleCountFlags) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// Swapchain image layers, 2 for stereo displays.
+    pub fn with_image_array_layers(mut self, image_array_layers: u32) -> Self {
+        self.image_array_layers = image_array_layers;
+        self
+    }
+
+    /// Renders the default pass once per view set in `view_mask`, into the swapchain image
+    /// layers. Needs the `multiview` feature and `with_image_array_layers` covering every view.
+    pub fn with_multiview(mut self, view_mask: u32) -> Self {
+        self.view_mask = view_mask;
+        self
+    }
+
+    /// Trades quality for memory on constrained devices: at most two frames in flight,
+    /// no MSAA, color-attachment-only swapchain images and a 16-bit depth buffer when
+    /// the device supports one.
+    pub fn with_low_memory_profile(mut self, low_memory_profile: bool) -> Self {
+        self.low_memory_profile = low_memory_profile;
+        self
+    }
+
+    /// Suballocates device local images and buffers from blocks of `block_size` bytes
+    /// instead of giving each one its own `vk::DeviceMemory`.
+    pub fn with_memory_allocator(mut self, block_size: vk::DeviceSize) -> Self {
+        self.memory_block_size = Some(block_size);
+        self
+    }
+
+    pub fn with_scorer(mut self, scorer: PhysicalDeviceScorer) -> Self {
+        self.scorer = Some(scorer);
+        self
+    }
+
+    pub fn with_queue_family(mut self, queue_family: u32) -> Self {
+        self.queue_family = Some(queue_family);
+        self
+    }
+
+    pub fn with_preferred_device_type(mut self, device_type: vk::PhysicalDeviceType) -> Self {
+        self.preferred_device_type = Some(device_type);
+        self
+    }
+
+    pub fn with_device_name_filter(mut self, name: &str) -> Self {
+        self.device_name_filter = Some(String::from(name));
+        self
+    }
+
+    /// Builds against the device at this position in `list_physical_devices`.
+    pub fn with_physical_device_index(mut self, index: usize) -> Self {
+        self.physical_device_index = Some(index);
+        self
+    }
+
+    /// Lists the devices the context could be built against, so the application can let the
+    /// user pick one. Needs the window to be set, as presentation support is checked.
+    pub fn list_physical_devices(&self) -> Result<Vec<PhysicalDeviceInfo>, VulkanError> {
+        let instance = Shared::new(self.create_instance(None)?);
+        let surface = self.create_surface(&instance)?;
+
+        let physical_devices = self
+            .physical_device_builder(Shared::clone(&instance), &surface)
+            .list_physical_devices()?;
+
+        Ok(physical_devices)
+    }
+
+    pub fn build(mut self) -> Result<VulkanContext, VulkanError> {
+        if self.low_memory_profile {
+            self.frames_count = self.frames_count.min(2);
+            self.sample_count = vk::SampleCountFlags::TYPE_1;
+        }
+
+        let window = self.window.ok_or_else(|| {
+            VulkanError::SurfaceError(String::from("Window not provided or not supported"))
+        })?;
+
+        let mut debug_callback = self.debug_callback.take();
+        if let Some(sink) = &self.validation_error_sink {
+            self.debug_options.debug_severity.error = true;
+            self.debug_options.debug_type.validation = true;
+            debug_callback = Some(count_validation_errors(Arc::clone(sink), debug_callback));
+        }
+        let instance = Shared::new(self.create_instance(debug_callback)?);
 
         let surface = self.create_surface(&instance)?;
 
-        let physical_device = self.select_physical_device(Rc::clone(&instance), &surface)?;
+        let scorer = self.scorer.take();
+        let physical_device =
+            self.select_physical_device(Shared::clone(&instance), &surface, scorer)?;
 
-        let device = Rc::new(self.create_logical_device(Rc::clone(&instance), &physical_device)?);
+        let device =
+            Shared::new(self.create_logical_device(Shared::clone(&instance), &physical_device)?);
 
-        let command_buffers = self.create_command_buffers(&physical_device, Rc::clone(&device))?;
+        let command_buffers =
+            self.create_command_buffers(&physical_device, Shared::clone(&device))?;
+
+        let sample_count = physical_device.clamp_sample_count(self.sample_count);
+
+        let sampler_cache = SamplerCache::new(
+            Shared::clone(&device),
+            physical_device.get_max_sampler_anisotropy(),
+        );
+
+        let memory_allocator = self.memory_block_size.map(|block_size| {
+            Shared::new(MemoryAllocator::new(
+                Shared::clone(&device),
+                block_size,
+                physical_device.get_limits().buffer_image_granularity,
+            ))
+        });
 
         let mut context = VulkanContext {
+            render_target_override: None,
             instance,
+            extensions: self.extensions,
+            features: self.features,
+            memory_block_size: self.memory_block_size,
             surface,
             physical_device,
             device,
             command_buffers,
+            sampler_cache,
+            memory_allocator,
             swapchain: None,
             depth_resources: None,
+            color_resources: None,
             render_pass: None,
             frame_buffers: None,
             frame_index: 0,
             frames_count: self.frames_count as usize,
+            command_buffers_per_frame: self.command_buffers_per_frame,
+            validation_error_sink: self.validation_error_sink.take(),
             back_buffer_index: 0,
             clear_value: [1.0, 1.0, 1.0, 1.0],
+            clear_values: vec![],
+            last_present_result: vk::Result::SUCCESS,
+            present_mode: self.present_mode,
+            desired_format: self.desired_format,
+            sample_count,
+            image_array_layers: self.image_array_layers,
+            view_mask: self.view_mask,
+            low_memory_profile: self.low_memory_profile,
+            is_shut_down: false,
+            _not_sync: PhantomData,
         };
 
-        context.resize(self.window.width, self.window.height)?;
+        context.resize(window.width(), window.height())?;
 
         Ok(context)
     }
 
-    fn create_instance(&self) -> Result<VulkanInstance, VulkanError> {
-        VulkanInstanceBuilder::new()
+    fn create_instance(
+        &self,
+        debug_callback: Option<DebugCallback>,
+    ) -> Result<VulkanInstance, VulkanError> {
+        let mut builder = VulkanInstanceBuilder::new()
             .with_debug_options(self.debug_options)
-            .with_application_info(&self.application_info)
-            .build()
+            .with_application_info(&self.application_info);
+
+        if let Some(debug_callback) = debug_callback {
+            builder = builder.with_debug_callback(debug_callback);
+        }
+
+        if let Some(window) = self.window {
+            builder = builder.with_window(window);
+        }
+
+        builder.build()
     }
 
     fn create_surface(&self, instance: &VulkanInstance) -> Result<Surface, VulkanError> {
-        SurfaceBuilder::new(instance)
-            .with_window(self.window)
-            .build()
+        let mut builder = SurfaceBuilder::new(instance);
+
+        if let Some(window) = self.window {
+            builder = builder.with_window(window);
+        }
+
+        builder.build()
     }
 
     fn select_physical_device(
         &self,
-        instance: Rc<VulkanInstance>,
+        instance: Shared<VulkanInstance>,
         surface: &Surface,
+        scorer: Option<PhysicalDeviceScorer>,
     ) -> Result<PhysicalDevice, VulkanError> {
-        PhysicalDeviceBuilder::new(instance, surface)
+        let mut builder = self.physical_device_builder(instance, surface);
+
+        if let Some(scorer) = scorer {
+            builder = builder.with_scorer(scorer);
+        }
+
+        builder.build()
+    }
+
+    fn physical_device_builder<'s>(
+        &self,
+        instance: Shared<VulkanInstance>,
+        surface: &'s Surface,
+    ) -> PhysicalDeviceBuilder<'s> {
+        let mut builder = PhysicalDeviceBuilder::new(instance, surface)
             .with_extensions(&self.extensions)
-            .with_features(self.features)
-            .build()
+            .with_features(self.features);
+
+        if let Some(queue_family) = self.queue_family {
+            builder = builder.with_queue_family(queue_family);
+        }
+
+        if let Some(device_type) = self.preferred_device_type {
+            builder = builder.with_preferred_device_type(device_type);
+        }
+
+        if let Some(name) = &self.device_name_filter {
+            builder = builder.with_device_name_filter(name);
+        }
+
+        if let Some(index) = self.physical_device_index {
+            builder = builder.with_physical_device_index(index);
+        }
+
+        builder
     }
 
     fn create_logical_device(
         &self,
-        instance: Rc<VulkanInstance>,
+        instance: Shared<VulkanInstance>,
         physical_device: &PhysicalDevice,
     ) -> Result<VulkanDevice, VulkanError> {
         VulkanDeviceBuilder::new(instance, physical_device)
@@ -362,10 +1380,27 @@ impl VulkanContextBuilder {
     fn create_command_buffers(
         &self,
         physical_device: &PhysicalDevice,
-        device: Rc<VulkanDevice>,
+        device: Shared<VulkanDevice>,
     ) -> Result<CommandBuffers, VulkanError> {
         CommandBuffersBuilder::new(physical_device, device)
             .with_frames_count(self.frames_count)
+            .with_command_buffers_per_frame(self.command_buffers_per_frame)
             .build()
     }
 }
+
+fn count_validation_errors(
+    sink: Arc<AtomicUsize>,
+    forward: Option<DebugCallback>,
+) -> DebugCallback {
+    Box::new(move |severity, ty, message| {
+        if severity.error && ty.validation {
+            sink.fetch_add(1, Ordering::SeqCst);
+        }
+
+        match &forward {
+            Some(forward) => forward(severity, ty, message),
+            None => debug::log_message(severity, ty, message),
+        }
+    })
+}
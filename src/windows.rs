@@ -1,5 +1,9 @@
-use std::os::raw::c_void;
-use std::ptr::null;
+use std::ffi::CStr;
+use std::os::raw::{c_ulong, c_void};
+use std::ptr::{null, null_mut};
+
+use ash::extensions::khr;
+use raw_window_handle::RawWindowHandle;
 
 #[derive(Copy, Clone)]
 pub struct Win32Window {
@@ -18,4 +22,151 @@ impl Default for Win32Window {
             height: 0,
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Copy, Clone)]
+pub struct XlibWindow {
+    pub display: *mut c_void,
+    pub window: c_ulong,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for XlibWindow {
+    fn default() -> Self {
+        XlibWindow {
+            display: null_mut(),
+            window: 0,
+            width: 0,
+            height: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct WaylandWindow {
+    pub display: *mut c_void,
+    pub surface: *mut c_void,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for WaylandWindow {
+    fn default() -> Self {
+        WaylandWindow {
+            display: null_mut(),
+            surface: null_mut(),
+            width: 0,
+            height: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum PlatformWindow {
+    #[cfg(windows)]
+    Win32(Win32Window),
+    #[cfg(unix)]
+    Xlib(XlibWindow),
+    #[cfg(unix)]
+    Wayland(WaylandWindow),
+}
+
+impl PlatformWindow {
+    pub fn from_raw_window_handle(
+        handle: RawWindowHandle,
+        width: u32,
+        height: u32,
+    ) -> Option<Self> {
+        match handle {
+            #[cfg(windows)]
+            RawWindowHandle::Windows(handle) => Some(PlatformWindow::Win32(Win32Window {
+                hinstance: handle.hinstance,
+                hwnd: handle.hwnd,
+                width,
+                height,
+            })),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            RawWindowHandle::Xlib(handle) => Some(PlatformWindow::Xlib(XlibWindow {
+                display: handle.display,
+                window: handle.window,
+                width,
+                height,
+            })),
+            #[cfg(any(
+                target_os = "linux",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd"
+            ))]
+            RawWindowHandle::Wayland(handle) => Some(PlatformWindow::Wayland(WaylandWindow {
+                display: handle.display,
+                surface: handle.surface,
+                width,
+                height,
+            })),
+            _ => None,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        match self {
+            #[cfg(windows)]
+            PlatformWindow::Win32(window) => window.width,
+            #[cfg(unix)]
+            PlatformWindow::Xlib(window) => window.width,
+            #[cfg(unix)]
+            PlatformWindow::Wayland(window) => window.width,
+        }
+    }
+
+    pub fn height(&self) -> u32 {
+        match self {
+            #[cfg(windows)]
+            PlatformWindow::Win32(window) => window.height,
+            #[cfg(unix)]
+            PlatformWindow::Xlib(window) => window.height,
+            #[cfg(unix)]
+            PlatformWindow::Wayland(window) => window.height,
+        }
+    }
+
+    pub fn surface_extension_name(&self) -> &'static CStr {
+        match self {
+            #[cfg(windows)]
+            PlatformWindow::Win32(_) => khr::Win32Surface::name(),
+            #[cfg(unix)]
+            PlatformWindow::Xlib(_) => khr::XlibSurface::name(),
+            #[cfg(unix)]
+            PlatformWindow::Wayland(_) => khr::WaylandSurface::name(),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl From<Win32Window> for PlatformWindow {
+    fn from(window: Win32Window) -> Self {
+        PlatformWindow::Win32(window)
+    }
+}
+
+#[cfg(unix)]
+impl From<XlibWindow> for PlatformWindow {
+    fn from(window: XlibWindow) -> Self {
+        PlatformWindow::Xlib(window)
+    }
+}
+
+#[cfg(unix)]
+impl From<WaylandWindow> for PlatformWindow {
+    fn from(window: WaylandWindow) -> Self {
+        PlatformWindow::Wayland(window)
+    }
+}
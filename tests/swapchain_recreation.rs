@@ -0,0 +1,143 @@
+#![cfg(unix)]
+
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::{c_char, c_int, c_uint, c_ulong, c_void};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use vulkan_bootstrap::debug::{DebugOptions, DebugSeverity, DebugType};
+use vulkan_bootstrap::vulkan_context::VulkanContextBuilder;
+use vulkan_bootstrap::windows::XlibWindow;
+
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 480;
+const RTLD_NOW: c_int = 2;
+
+extern "C" {
+    fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+}
+
+type XOpenDisplay = unsafe extern "C" fn(*const c_char) -> *mut c_void;
+type XDefaultRootWindow = unsafe extern "C" fn(*mut c_void) -> c_ulong;
+type XCreateSimpleWindow = unsafe extern "C" fn(
+    *mut c_void,
+    c_ulong,
+    c_int,
+    c_int,
+    c_uint,
+    c_uint,
+    c_uint,
+    c_ulong,
+    c_ulong,
+) -> c_ulong;
+type XWindowCall = unsafe extern "C" fn(*mut c_void, c_ulong) -> c_int;
+type XDisplayCall = unsafe extern "C" fn(*mut c_void) -> c_int;
+
+// Xlib is loaded at runtime so that the test suite does not link against it.
+struct Xlib {
+    library: *mut c_void,
+}
+
+impl Xlib {
+    fn load() -> Option<Self> {
+        let name = CStr::from_bytes_with_nul(b"libX11.so.6\0").unwrap();
+        let library = unsafe { dlopen(name.as_ptr(), RTLD_NOW) };
+        if library.is_null() {
+            None
+        } else {
+            Some(Xlib { library })
+        }
+    }
+
+    unsafe fn get<T: Copy>(&self, name: &[u8]) -> T {
+        let name = CStr::from_bytes_with_nul(name).unwrap();
+        let symbol = dlsym(self.library, name.as_ptr());
+        assert!(!symbol.is_null(), "missing Xlib symbol {:?}", name);
+        mem::transmute_copy(&symbol)
+    }
+
+    fn create_window(&self) -> Option<XlibWindow> {
+        unsafe {
+            let open_display: XOpenDisplay = self.get(b"XOpenDisplay\0");
+            let display = open_display(ptr::null());
+            if display.is_null() {
+                return None;
+            }
+
+            let default_root_window: XDefaultRootWindow = self.get(b"XDefaultRootWindow\0");
+            let create_simple_window: XCreateSimpleWindow = self.get(b"XCreateSimpleWindow\0");
+            let map_window: XWindowCall = self.get(b"XMapWindow\0");
+            let flush: XDisplayCall = self.get(b"XFlush\0");
+
+            let window = create_simple_window(
+                display,
+                default_root_window(display),
+                0,
+                0,
+                WIDTH,
+                HEIGHT,
+                0,
+                0,
+                0,
+            );
+            map_window(display, window);
+            flush(display);
+
+            Some(XlibWindow {
+                display,
+                window,
+                width: WIDTH,
+                height: HEIGHT,
+            })
+        }
+    }
+
+    fn destroy_window(&self, window: XlibWindow) {
+        unsafe {
+            let destroy_window: XWindowCall = self.get(b"XDestroyWindow\0");
+            let close_display: XDisplayCall = self.get(b"XCloseDisplay\0");
+            destroy_window(window.display, window.window);
+            close_display(window.display);
+        }
+    }
+}
+
+#[test]
+#[ignore = "needs a GPU, an X11 display and the Khronos validation layer"]
+fn recreating_the_swapchain_does_not_leak() {
+    let xlib = Xlib::load().expect("libX11 is not available");
+    let window = xlib.create_window().expect("no X11 display");
+
+    // The validation layer reports swapchains that are still alive when the device is destroyed.
+    let validation_errors = Arc::new(AtomicUsize::new(0));
+    let mut context = VulkanContextBuilder::new()
+        .with_window(window)
+        .with_debug_options(DebugOptions {
+            debug_severity: DebugSeverity {
+                error: true,
+                ..DebugSeverity::none()
+            },
+            debug_type: DebugType {
+                validation: true,
+                ..DebugType::none()
+            },
+            ..DebugOptions::default()
+        })
+        .with_validation_error_sink(Arc::clone(&validation_errors))
+        .build()
+        .unwrap();
+
+    for _ in 0..16 {
+        let old_swapchain = context.get_swapchain().get();
+        context.recreate_swapchain(WIDTH, HEIGHT).unwrap();
+        assert_ne!(context.get_swapchain().get(), old_swapchain);
+    }
+
+    context.shutdown().unwrap();
+    xlib.destroy_window(window);
+
+    assert_eq!(validation_errors.load(Ordering::SeqCst), 0);
+}